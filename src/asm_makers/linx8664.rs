@@ -1,4 +1,4 @@
-use crate::init::{LinuxX8664, Variables};
+use crate::init::{ArrayInit, ElemType, LinuxX8664, Section, StructLayout, Variables};
 use std::fmt::Write;
 
 pub static INDENT: &str = "    ";
@@ -6,64 +6,310 @@ pub static INDENT: &str = "    ";
 /// Generates the assembly code for a Linux x86-64 executable.
 ///
 /// This function takes a `LinuxX8664` struct, which contains the necessary information to generate the assembly code, and returns a `String` containing the complete assembly code.
-/// The assembly code is divided into three sections: `.text`, `.data`, and `.bss`. The `.text` section contains the executable instructions, the `.data` section contains initialized data, and the `.bss` section contains uninitialized data.
+/// The assembly code is divided into the sections `.text`, `.data`, `.rodata` and `.bss`. The `.text` section contains the executable instructions, the `.data` section contains initialized data, the `.rodata` section contains constants and the `.bss` section contains uninitialized data.
+/// Variables and functions placed with `place_in_section` go into their custom section after these, in the order the sections were first used.
 /// The function iterates over the variables in the `LinuxX8664` struct and generates the appropriate assembly instructions for each variable type, such as `db`, `dw`, `dd`, `dq`, and `db` for strings.
 /// The function also generates the necessary labels and global symbols for the assembly code.
 pub fn mk_asm_linx8664(xasm: &LinuxX8664) -> String {
     let mut asm = String::with_capacity(2048);
     let mut txtsec = String::with_capacity(512);
-    write!(txtsec, "section .text\n{}global _start\n_start:\n",INDENT).unwrap();
+    writeln!(txtsec, "section .text\n{}global _start\n_start:",INDENT).unwrap();
     let mut datasec = String::with_capacity(1024);
-    write!(datasec,"section .data\n").unwrap();
+    writeln!(datasec,"section .data").unwrap();
+    let mut rodatasec = String::with_capacity(1024);
+    writeln!(rodatasec,"section .rodata").unwrap();
     let mut bsssec = String::with_capacity(512);
-    write!(bsssec,"section .bss\n").unwrap();
+    writeln!(bsssec,"section .bss").unwrap();
+    let mut custom: Vec<(Section, String)> = Vec::new();
     let mut funcs = String::with_capacity(512);
     for funs in xasm.dump().3{
-        funcs.push_str(format!("{}:\n",funs.name).as_str());
+        let out = match xasm.section_of(funs.name) {
+            Some(section) => custom_section(&mut custom, section),
+            None => &mut funcs,
+        };
+        if let Some(align) = xasm.align_of(funs.name) {
+            writeln!(out, "align {}", align).unwrap();
+        }
+        out.push_str(format!("{}:\n",funs.name).as_str());
         for inst in &funs.body{
-            funcs.push_str(format!("{}{}\n",INDENT,inst).as_str());
+            out.push_str(format!("{}{}\n",INDENT,inst).as_str());
         }
     }
     for vars in xasm.dump().1 {
-        match vars.1 {
-            Variables::I8(val) => write!(datasec, "{}{}: db {}\n", INDENT, vars.0, val).unwrap(),
-            Variables::I16(val) => write!(datasec, "{}{}: dw {}\n", INDENT, vars.0, val).unwrap(),
-            Variables::I32(val) => write!(datasec, "{}{}: dd {}\n", INDENT, vars.0, val).unwrap(),
-            Variables::I64(val) => write!(datasec, "{}{}: dq {}\n", INDENT, vars.0, val).unwrap(),
-            Variables::U8(val) => write!(datasec, "{}{}: db {}\n", INDENT, vars.0, val).unwrap(),
-            Variables::U16(val) => write!(datasec, "{}{}: dw {}\n", INDENT, vars.0, val).unwrap(),
-            Variables::U32(val) => write!(datasec, "{}{}: dd {}\n", INDENT, vars.0, val).unwrap(),
-            Variables::U64(val) => write!(datasec, "{}{}: dq {}\n", INDENT, vars.0, val).unwrap(),
-            Variables::F32(val) => write!(datasec, "{}{}: dd {}\n", INDENT, vars.0, val).unwrap(),
-            Variables::F64(val) => write!(datasec, "{}{}: dq {}\n", INDENT, vars.0, val).unwrap(),
-            Variables::Str(ref val) => write!(datasec, "{}{}: db \"{}\", 0\n", INDENT, vars.0, val).unwrap(),
-            Variables::Bool(val) => write!(datasec, "{}{}: db {}\n", INDENT, vars.0, if val { 1 } else { 0 }).unwrap(),
-            Variables::AsIs(code) => write!(datasec, "{}{}\n", INDENT, code).unwrap(),
-        }
+        write_data(xasm, section_for(xasm, vars.0, &mut custom, &mut datasec), vars.0, vars.1);
     }
+    for vars in xasm.readonly_variables() {
+        write_data(xasm, section_for(xasm, vars.0, &mut custom, &mut rodatasec), vars.0, vars.1);
+    }
+    write_pool(xasm, &mut rodatasec);
     for vars in xasm.dump().2{
+        let out = section_for(xasm, vars.0, &mut custom, &mut bsssec);
         match vars.1 {
-            Variables::I8(_) => write!(bsssec, "{}{}: resb {}\n", INDENT, vars.0, 1).unwrap(),
-            Variables::I16(_) => write!(bsssec, "{}{}: resw {}\n", INDENT, vars.0, 1).unwrap(),
-            Variables::I32(_) => write!(bsssec, "{}{}: resd {}\n", INDENT, vars.0, 1).unwrap(),
-            Variables::I64(_) => write!(bsssec, "{}{}: resq {}\n", INDENT, vars.0, 1).unwrap(),
-            Variables::U8(_) => write!(bsssec, "{}{}: resb {}\n", INDENT, vars.0, 1).unwrap(),
-            Variables::U16(_) => write!(bsssec, "{}{}: resw {}\n", INDENT, vars.0, 1).unwrap(),
-            Variables::U32(_) => write!(bsssec, "{}{}: resd {}\n", INDENT, vars.0, 1).unwrap(),
-            Variables::U64(_) => write!(bsssec, "{}{}: resq {}\n", INDENT, vars.0, 1).unwrap(),
-            Variables::F32(_) => write!(bsssec, "{}{}: resd {}\n", INDENT, vars.0, 1).unwrap(),
-            Variables::F64(_) => write!(bsssec, "{}{}: resq {}\n", INDENT, vars.0, 1).unwrap(),
-            Variables::Str(ref val) => write!(bsssec, "{}{}: resb {}\n", INDENT, vars.0, val.len() + 1).unwrap(),
-            Variables::Bool(_) => write!(bsssec, "{}{}: resb {}\n", INDENT, vars.0, 1).unwrap(),
-            Variables::AsIs(code) => write!(bsssec, "{}{}\n", INDENT, code).unwrap(),
-        }
-    }
-    for node in xasm.dump().0 {
-        write!(txtsec, "{}{}{}\n",INDENT, INDENT, node).unwrap();
+            Variables::AsIs(code) => writeln!(out, "{}{}", INDENT, code).unwrap(),
+            var => {
+                write_align(xasm, out, vars.0, var, "alignb");
+                writeln!(out, "{}{}: {}", INDENT, vars.0, bss_directive(var)).unwrap()
+            }
+        }
+    }
+    for node in xasm.startup().iter().chain(xasm.dump().0) {
+        writeln!(txtsec, "{}{}{}",INDENT, INDENT, node).unwrap();
     }
     asm.push_str(&txtsec);
     asm.push_str(&funcs);
     asm.push_str(&datasec);
+    if !xasm.readonly_variables().is_empty() || !xasm.pooled_strings().is_empty() || !xasm.pooled_constants().is_empty() {
+        asm.push_str(&rodatasec);
+    }
     asm.push_str(&bsssec);
+    for (_, sec) in custom {
+        asm.push_str(&sec);
+    }
     asm
 }
+
+/// the buffer of a custom section, started with its ``section`` line on first use
+fn custom_section(custom: &mut Vec<(Section, String)>, section: Section) -> &mut String {
+    let pos = match custom.iter().position(|(s, _)| s.name == section.name) {
+        Some(pos) => pos,
+        None => {
+            custom.push((section, format!("section {} {}\n", section.name, section.flags)));
+            custom.len() - 1
+        }
+    };
+    &mut custom[pos].1
+}
+
+/// the custom section ``name`` was placed in, or ``default``
+fn section_for<'a>(
+    xasm: &LinuxX8664,
+    name: &str,
+    custom: &'a mut Vec<(Section, String)>,
+    default: &'a mut String,
+) -> &'a mut String {
+    match xasm.section_of(name) {
+        Some(section) => custom_section(custom, section),
+        None => default,
+    }
+}
+
+/// writes ``directive`` (``align`` or ``alignb``) for the larger of the alignment set with ``set_align``
+/// and the natural alignment of a struct
+fn write_align(xasm: &LinuxX8664, out: &mut String, name: &str, var: Variables, directive: &str) {
+    let natural = match var {
+        Variables::Struct { layout, .. } => layout.align(),
+        _ => 1,
+    };
+    let align = xasm.align_of(name).unwrap_or(1).max(natural);
+    if align > 1 {
+        writeln!(out, "{}{} {}", INDENT, directive, align).unwrap();
+    }
+}
+
+/// writes the constant pool: scalars largest first so they stay naturally aligned, then the strings.
+/// a string that ends another pooled string is not written again but becomes an ``equ`` into it
+fn write_pool(xasm: &LinuxX8664, out: &mut String) {
+    let mut constants = xasm.pooled_constants().to_vec();
+    constants.sort_by_key(|(_, var)| std::cmp::Reverse(var.size()));
+    if !constants.is_empty() {
+        writeln!(out, "{}align 8", INDENT).unwrap();
+    }
+    for (label, var) in constants {
+        writeln!(out, "{}{}: {}", INDENT, label, data_directive(label, var)).unwrap();
+    }
+    let strings: Vec<(&str, Vec<u8>)> = xasm
+        .pooled_strings()
+        .iter()
+        .map(|(label, var)| (*label, format!("{}\0", var.get_value()).into_bytes()))
+        .collect();
+    // sorted by their reversed bytes, a string that is a suffix of another sorts right before the
+    // strings it ends, so walking backwards every string finds the longest string it is a suffix of
+    let mut order: Vec<usize> = (0..strings.len()).collect();
+    order.sort_by(|&a, &b| strings[a].1.iter().rev().cmp(strings[b].1.iter().rev()));
+    let mut host: Vec<usize> = (0..strings.len()).collect();
+    for pair in order.windows(2).rev() {
+        let (short, long) = (&strings[pair[0]].1, &strings[pair[1]].1);
+        if long.ends_with(short) {
+            host[pair[0]] = host[pair[1]];
+        }
+    }
+    for (i, (label, bytes)) in strings.iter().enumerate() {
+        if host[i] == i {
+            writeln!(out, "{}{}: db {}", INDENT, label, nasm_bytes(bytes)).unwrap();
+        }
+    }
+    for (i, (label, bytes)) in strings.iter().enumerate() {
+        let (host_label, host_bytes) = &strings[host[i]];
+        if host[i] != i {
+            writeln!(out, "{}{} equ {} + {}", INDENT, label, host_label, host_bytes.len() - bytes.len()).unwrap();
+        }
+    }
+}
+
+fn write_data(xasm: &LinuxX8664, out: &mut String, name: &str, var: Variables) {
+    for target in var.targets() {
+        if !xasm.has_symbol(target) {
+            panic!("`{}` points to `{}`, which is not defined", name, target);
+        }
+    }
+    match var {
+        Variables::AsIs(code) => writeln!(out, "{}{}", INDENT, code).unwrap(),
+        var => {
+            write_align(xasm, out, name, var, "align");
+            writeln!(out, "{}{}: {}", INDENT, name, data_directive(name, var)).unwrap()
+        }
+    }
+}
+
+/// reservation for a variable in ``.bss``
+fn bss_directive(var: Variables) -> String {
+    match var {
+        Variables::I8(_) | Variables::U8(_) | Variables::Bool(_) => "resb 1".to_string(),
+        Variables::I16(_) | Variables::U16(_) => "resw 1".to_string(),
+        Variables::I32(_) | Variables::U32(_) | Variables::F32(_) => "resd 1".to_string(),
+        Variables::I64(_) | Variables::U64(_) | Variables::F64(_) | Variables::Ptr { .. } => "resq 1".to_string(),
+        Variables::Str(val) => format!("resb {}", val.len() + 1),
+        Variables::Bytes(val) => format!("resb {}", val.len()),
+        Variables::AsIs(code) => code.to_string(),
+        Variables::Array { elem, len, .. } => format!("{} {}", elem.bss_directive(), len),
+        Variables::Struct { layout, .. } => format!("resb {}", layout.size()),
+    }
+}
+
+/// data directives for a value in ``.data``, lines after the first are indented
+fn data_directive(name: &str, var: Variables) -> String {
+    match var {
+        Variables::I8(val) => format!("db {}", val),
+        Variables::I16(val) => format!("dw {}", val),
+        Variables::I32(val) => format!("dd {}", val),
+        Variables::I64(val) => format!("dq {}", val),
+        Variables::U8(val) => format!("db {}", val),
+        Variables::U16(val) => format!("dw {}", val),
+        Variables::U32(val) => format!("dd {}", val),
+        Variables::U64(val) => format!("dq {}", val),
+        // floats are written as their bit pattern so whole numbers, inf and nan survive nasm
+        Variables::F32(val) => format!("dd {:#010x} ; {}", val.to_bits(), val),
+        Variables::F64(val) => format!("dq {:#018x} ; {}", val.to_bits(), val),
+        Variables::Str(val) => format!("db {}", nasm_bytes(&[val.as_bytes(), &[0]].concat())),
+        // an empty blob still needs its label
+        Variables::Bytes([]) => "times 0 db 0".to_string(),
+        Variables::Bytes(val) => format!("db {}", nasm_bytes(val)),
+        Variables::Ptr { .. } => format!("dq {}", var.get_value()),
+        Variables::Bool(val) => format!("db {}", if val { 1 } else { 0 }),
+        Variables::AsIs(code) => code.to_string(),
+        Variables::Array { elem, len, init } => array_data(name, elem, len, init),
+        Variables::Struct { layout, init } => struct_data(name, layout, init),
+    }
+}
+
+/// data directives for a struct in ``.data`` with zeroed padding, ``init`` replaces the layout's field values
+fn struct_data(name: &str, layout: &StructLayout, init: &[Variables]) -> String {
+    let fields = layout.offsets();
+    if !init.is_empty() && init.len() != fields.len() {
+        panic!("struct `{}` of type `{}` has {} fields but {} initializers", name, layout.name, fields.len(), init.len());
+    }
+    let mut lines = Vec::new();
+    let mut end = 0;
+    for (i, (field, offset, kind)) in fields.into_iter().enumerate() {
+        let value = init.get(i).copied().unwrap_or(kind);
+        if !value.match_var(&kind) || value.size() != kind.size() {
+            panic!("struct `{}`: the initializer of `{}` does not match its type {:?}", name, field, kind);
+        }
+        if offset > end {
+            lines.push(format!("times {} db 0", offset - end));
+        }
+        lines.push(data_directive(field, value));
+        end = offset + kind.size();
+    }
+    if layout.size() > end || lines.is_empty() {
+        lines.push(format!("times {} db 0", layout.size() - end));
+    }
+    lines.join(&format!("\n{}", INDENT))
+}
+
+/// operands of ``db`` for arbitrary bytes: printable ascii goes into double quoted runs, quotes,
+/// control characters and non ascii bytes are written as numbers
+fn nasm_bytes(bytes: &[u8]) -> String {
+    let mut parts = Vec::new();
+    let mut run = String::new();
+    for &byte in bytes {
+        if (b' '..=b'~').contains(&byte) && byte != b'"' {
+            run.push(byte as char);
+            continue;
+        }
+        if !run.is_empty() {
+            parts.push(format!("\"{}\"", run));
+            run.clear();
+        }
+        parts.push(byte.to_string());
+    }
+    if !run.is_empty() {
+        parts.push(format!("\"{}\"", run));
+    }
+    parts.join(", ")
+}
+
+/// data directives for an array in ``.data``, missing elements of an initializer list are 0
+fn array_data(name: &str, elem: ElemType, len: usize, init: ArrayInit) -> String {
+    let dir = elem.data_directive();
+    let float = |val: f64| match elem {
+        ElemType::F32 => format!("{:#010x}", (val as f32).to_bits()),
+        _ => format!("{:#018x}", val.to_bits()),
+    };
+    let values: Vec<String> = match init {
+        ArrayInit::Zero => Vec::new(),
+        ArrayInit::Ptrs(_) if !matches!(elem, ElemType::I64 | ElemType::U64) => {
+            panic!("array `{}` holds pointers, its elements must be I64 or U64", name)
+        }
+        ArrayInit::Ptrs(targets) => targets.iter().map(|target| target.to_string()).collect(),
+        ArrayInit::Fill(_) | ArrayInit::List(_) if elem.is_float() => {
+            panic!("array `{}` holds floats, use FillFloat or ListFloat", name)
+        }
+        ArrayInit::FillFloat(_) | ArrayInit::ListFloat(_) if !elem.is_float() => {
+            panic!("array `{}` holds integers, use Fill or List", name)
+        }
+        ArrayInit::Fill(val) => return format!("times {} {} {}", len, dir, val),
+        ArrayInit::FillFloat(val) => return format!("times {} {} {}", len, dir, float(val)),
+        ArrayInit::List(vals) => vals.iter().map(|val| val.to_string()).collect(),
+        ArrayInit::ListFloat(vals) => vals.iter().map(|val| float(*val)).collect(),
+    };
+    if values.len() > len {
+        panic!("array `{}` has {} elements but {} initializers", name, len, values.len());
+    }
+    if values.is_empty() {
+        return format!("times {} {} 0", len, dir);
+    }
+    let mut data = format!("{} {}", dir, values.join(", "));
+    if values.len() < len {
+        write!(data, "\n{}times {} {} 0", INDENT, len - values.len(), dir).unwrap();
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static POINT: StructLayout = StructLayout {
+        name: "Point",
+        fields: &[("x", Variables::I32(0)), ("y", Variables::I32(0))],
+    };
+
+    #[test]
+    fn struct_initializers_are_checked() {
+        let data = struct_data("p", &POINT, &[Variables::I32(1), Variables::I32(-2)]);
+        assert_eq!(data, format!("dd 1\n{}dd -2", INDENT));
+    }
+
+    #[test]
+    #[should_panic(expected = "the initializer of `y` does not match its type")]
+    fn mismatched_struct_initializer_panics() {
+        struct_data("p", &POINT, &[Variables::I32(1), Variables::F32(2.0)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "the initializer of `x` does not match its type")]
+    fn signedness_mismatch_panics() {
+        struct_data("p", &POINT, &[Variables::U32(1), Variables::I32(2)]);
+    }
+}
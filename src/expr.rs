@@ -0,0 +1,565 @@
+use std::ops;
+use crate::{
+    init::{ElemType, LinuxX8664, Register, Variables},
+    instructions::{Condition, Instruction},
+    xstd::Xstd,
+};
+
+/// binary operators of ``Expr``, on 64 bit values with wrapping arithmetic
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    /// signed division, rounding toward 0
+    Div,
+    /// signed remainder, with the sign of the dividend
+    Rem,
+    UDiv,
+    URem,
+    And,
+    Or,
+    Xor,
+    Shl,
+    /// arithmetic shift right, keeps the sign
+    Sar,
+    /// logical shift right, shifts in zeros
+    Shr,
+}
+
+/// an integer expression, built with the constructors and the ``+ - * / % & | ^ << >>`` operators
+/// (``/``, ``%`` and ``>>`` are signed) and lowered to instructions by ``compile``
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    Const(i64),
+    /// an integer, ``Bool`` or ``Ptr`` variable, sign or zero extended from its width
+    Var(&'static str),
+    Reg(Register),
+    Bin(BinOp, Box<Expr>, Box<Expr>),
+    /// 1 if the operands compare as ``Condition`` says, otherwise 0
+    Cmp(Condition, Box<Expr>, Box<Expr>),
+    /// truncates to the width of the type and sign or zero extends back to 64 bits, ``Bool`` becomes 0 or 1
+    Cast(Box<Expr>, ElemType),
+    Neg(Box<Expr>),
+    /// bitwise not
+    Not(Box<Expr>),
+}
+
+impl Expr {
+    pub fn var(name: &'static str) -> Self {
+        Expr::Var(name)
+    }
+    pub fn reg(reg: Register) -> Self {
+        Expr::Reg(reg)
+    }
+    pub fn bin(op: BinOp, lhs: Expr, rhs: Expr) -> Self {
+        Expr::Bin(op, Box::new(lhs), Box::new(rhs))
+    }
+    pub fn cmp(condition: Condition, lhs: Expr, rhs: Expr) -> Self {
+        Expr::Cmp(condition, Box::new(lhs), Box::new(rhs))
+    }
+    pub fn cast(self, ty: ElemType) -> Self {
+        if ty.is_float() {
+            panic!("Expr::cast: {:?} is not an integer type", ty);
+        }
+        Expr::Cast(Box::new(self), ty)
+    }
+    pub fn udiv(self, rhs: Expr) -> Self {
+        Expr::bin(BinOp::UDiv, self, rhs)
+    }
+    pub fn urem(self, rhs: Expr) -> Self {
+        Expr::bin(BinOp::URem, self, rhs)
+    }
+    /// logical shift right, ``>>`` shifts arithmetically
+    pub fn ushr(self, rhs: Expr) -> Self {
+        Expr::bin(BinOp::Shr, self, rhs)
+    }
+
+    /// the expression with constant subtrees evaluated and operations that do nothing removed
+    pub fn fold(&self) -> Expr {
+        match self {
+            Expr::Bin(op, lhs, rhs) => fold_bin(*op, lhs.fold(), rhs.fold()),
+            Expr::Cmp(condition, lhs, rhs) => match (lhs.fold(), rhs.fold()) {
+                (Expr::Const(a), Expr::Const(b)) => Expr::Const(condition.holds(a, b) as i64),
+                (lhs, rhs) => Expr::cmp(*condition, lhs, rhs),
+            },
+            Expr::Cast(inner, ty) => match inner.fold() {
+                Expr::Const(value) => Expr::Const(truncate(value, *ty)),
+                inner => Expr::Cast(Box::new(inner), *ty),
+            },
+            Expr::Neg(inner) => match inner.fold() {
+                Expr::Const(value) => Expr::Const(value.wrapping_neg()),
+                inner => Expr::Neg(Box::new(inner)),
+            },
+            Expr::Not(inner) => match inner.fold() {
+                Expr::Const(value) => Expr::Const(!value),
+                inner => Expr::Not(Box::new(inner)),
+            },
+            leaf => leaf.clone(),
+        }
+    }
+
+    /// the amount of registers needed to evaluate the expression (its Sethi–Ullman number),
+    /// a constant that fits an instruction's immediate needs none
+    pub fn registers_needed(&self) -> usize {
+        match self {
+            Expr::Const(_) | Expr::Var(_) | Expr::Reg(_) => 1,
+            Expr::Bin(op, lhs, rhs) if immediate(*op, rhs).is_some() => lhs.registers_needed(),
+            Expr::Cmp(_, lhs, rhs) if matches!(**rhs, Expr::Const(c) if i32::try_from(c).is_ok()) => {
+                lhs.registers_needed()
+            }
+            Expr::Bin(_, lhs, rhs) | Expr::Cmp(_, lhs, rhs) => {
+                let (l, r) = (lhs.registers_needed(), rhs.registers_needed());
+                if l == r { l + 1 } else { l.max(r) }
+            }
+            Expr::Cast(inner, _) | Expr::Neg(inner) | Expr::Not(inner) => inner.registers_needed(),
+        }
+    }
+
+    /// folds the expression and emits code computing it into a register taken from the register allocator,
+    /// which the caller releases with ``free_reg``. rsp, rbp and the registers the expression reads are never
+    /// handed out, rax, rcx and rdx are clobbered by divisions and shifts by a non constant amount
+    pub fn compile(&self, x: &mut LinuxX8664) -> Register {
+        let expr = self.fold();
+        let mut reserved = vec![Register::rsp, Register::rbp, Register::rax, Register::rcx, Register::rdx];
+        expr.regs(&mut reserved);
+        let mut compiler = Compiler { x, reserved, parked: Vec::new() };
+        let expr = compiler.save_scratch_reads(expr);
+        let result = compiler.gen(&expr);
+        for reg in std::mem::take(&mut compiler.parked) {
+            compiler.x.free_reg(reg);
+        }
+        result
+    }
+
+    /// like ``compile`` but leaves the result in ``dst``
+    pub fn compile_into(&self, x: &mut LinuxX8664, dst: Register) {
+        let result = self.compile(x);
+        if result != dst {
+            x.emit(Instruction::Mov { dst, src: result });
+        }
+        x.free_reg(result);
+    }
+
+    fn regs(&self, out: &mut Vec<Register>) {
+        match self {
+            Expr::Reg(reg) => out.push(*reg),
+            Expr::Bin(_, lhs, rhs) | Expr::Cmp(_, lhs, rhs) => {
+                lhs.regs(out);
+                rhs.regs(out);
+            }
+            Expr::Cast(inner, _) | Expr::Neg(inner) | Expr::Not(inner) => inner.regs(out),
+            Expr::Const(_) | Expr::Var(_) => {}
+        }
+    }
+
+    fn uses_scratch(&self) -> bool {
+        match self {
+            Expr::Bin(op, lhs, rhs) => {
+                let scratch = match op {
+                    BinOp::Div | BinOp::Rem | BinOp::UDiv | BinOp::URem => true,
+                    BinOp::Shl | BinOp::Sar | BinOp::Shr => immediate(*op, rhs).is_none(),
+                    _ => false,
+                };
+                scratch || lhs.uses_scratch() || rhs.uses_scratch()
+            }
+            Expr::Cmp(_, lhs, rhs) => lhs.uses_scratch() || rhs.uses_scratch(),
+            Expr::Cast(inner, _) | Expr::Neg(inner) | Expr::Not(inner) => inner.uses_scratch(),
+            Expr::Const(_) | Expr::Var(_) | Expr::Reg(_) => false,
+        }
+    }
+
+    fn map_regs(self, from: Register, to: Register) -> Expr {
+        let map = |e: Box<Expr>| Box::new(e.map_regs(from, to));
+        match self {
+            Expr::Reg(reg) if reg == from => Expr::Reg(to),
+            Expr::Bin(op, lhs, rhs) => Expr::Bin(op, map(lhs), map(rhs)),
+            Expr::Cmp(condition, lhs, rhs) => Expr::Cmp(condition, map(lhs), map(rhs)),
+            Expr::Cast(inner, ty) => Expr::Cast(map(inner), ty),
+            Expr::Neg(inner) => Expr::Neg(map(inner)),
+            Expr::Not(inner) => Expr::Not(map(inner)),
+            leaf => leaf,
+        }
+    }
+}
+
+/// the right operand as an immediate if the operator has an immediate form and the constant fits it
+fn immediate(op: BinOp, rhs: &Expr) -> Option<i32> {
+    let value = match rhs {
+        Expr::Const(value) => *value,
+        _ => return None,
+    };
+    match op {
+        BinOp::Shl | BinOp::Sar | BinOp::Shr => Some((value & 63) as i32),
+        BinOp::Div | BinOp::Rem | BinOp::UDiv | BinOp::URem => None,
+        _ => i32::try_from(value).ok(),
+    }
+}
+
+fn truncate(value: i64, ty: ElemType) -> i64 {
+    match ty {
+        ElemType::I8 => value as i8 as i64,
+        ElemType::I16 => value as i16 as i64,
+        ElemType::I32 => value as i32 as i64,
+        ElemType::U8 => value as u8 as i64,
+        ElemType::U16 => value as u16 as i64,
+        ElemType::U32 => value as u32 as i64,
+        ElemType::Bool => (value != 0) as i64,
+        ElemType::I64 | ElemType::U64 | ElemType::F32 | ElemType::F64 => value,
+    }
+}
+
+fn fold_bin(op: BinOp, lhs: Expr, rhs: Expr) -> Expr {
+    use BinOp::*;
+    if let (Expr::Const(a), Expr::Const(b)) = (&lhs, &rhs) {
+        let (a, b) = (*a, *b);
+        if b == 0 && matches!(op, Div | Rem | UDiv | URem) {
+            panic!("Expr: division by zero in a constant expression");
+        }
+        return Expr::Const(match op {
+            Add => a.wrapping_add(b),
+            Sub => a.wrapping_sub(b),
+            Mul => a.wrapping_mul(b),
+            Div => a.wrapping_div(b),
+            Rem => a.wrapping_rem(b),
+            UDiv => ((a as u64) / (b as u64)) as i64,
+            URem => ((a as u64) % (b as u64)) as i64,
+            And => a & b,
+            Or => a | b,
+            Xor => a ^ b,
+            Shl => a.wrapping_shl(b as u32 & 63),
+            Sar => a.wrapping_shr(b as u32 & 63),
+            Shr => ((a as u64) >> (b as u32 & 63)) as i64,
+        });
+    }
+    // constants go to the right of commutative operators so they become immediates
+    let (lhs, rhs) = match (op, &lhs) {
+        (Add | Mul | And | Or | Xor, Expr::Const(_)) => (rhs, lhs),
+        _ => (lhs, rhs),
+    };
+    match (op, &rhs) {
+        (Add | Sub | Or | Xor | Shl | Sar | Shr, Expr::Const(0)) => lhs,
+        (Mul | Div | UDiv, Expr::Const(1)) => lhs,
+        (And, Expr::Const(-1)) => lhs,
+        (Mul | And, Expr::Const(0)) => Expr::Const(0),
+        _ => Expr::bin(op, lhs, rhs),
+    }
+}
+
+struct Compiler<'x> {
+    x: &'x mut LinuxX8664,
+    /// registers that must not hold temporaries
+    reserved: Vec<Register>,
+    /// reserved registers the allocator handed out and copies of read registers, given back when compiling is done
+    parked: Vec<Register>,
+}
+
+impl Compiler<'_> {
+    fn temp(&mut self) -> Register {
+        loop {
+            let reg = self.x.alloc_reg(false);
+            if !self.reserved.contains(&reg) {
+                return reg;
+            }
+            self.parked.push(reg);
+        }
+    }
+
+    fn asm(&mut self, code: String) {
+        self.x.emit(Instruction::AsIs(Box::leak(code.into_boxed_str())));
+    }
+
+    /// copies the registers divisions and shifts clobber out of the way before anything is computed
+    fn save_scratch_reads(&mut self, mut expr: Expr) -> Expr {
+        if !expr.uses_scratch() {
+            return expr;
+        }
+        for scratch in [Register::rax, Register::rcx, Register::rdx] {
+            let mut read = Vec::new();
+            expr.regs(&mut read);
+            if read.contains(&scratch) {
+                let copy = self.temp();
+                self.parked.push(copy);
+                self.x.emit(Instruction::Mov { dst: copy, src: scratch });
+                expr = expr.map_regs(scratch, copy);
+            }
+        }
+        expr
+    }
+
+    /// emits ``expr`` into a temporary, the subtree needing more registers goes first
+    fn gen(&mut self, expr: &Expr) -> Register {
+        match expr {
+            Expr::Const(value) => {
+                let dst = self.temp();
+                self.x.emit(Instruction::MovImm { dst, imm: *value });
+                dst
+            }
+            Expr::Reg(reg) => {
+                let dst = self.temp();
+                self.x.emit(Instruction::Mov { dst, src: *reg });
+                dst
+            }
+            Expr::Var(name) => {
+                let kind = match self.x.get_variable(name) {
+                    Some(kind) if kind.is_integer() || matches!(kind, Variables::Bool(_) | Variables::Ptr { .. }) => kind,
+                    _ => panic!("Expr: `{}` is not an integer variable", name),
+                };
+                let dst = self.temp();
+                Xstd::new(self.x).load_var(dst, name, kind);
+                dst
+            }
+            Expr::Bin(op, lhs, rhs) => {
+                if let Some(imm) = immediate(*op, rhs) {
+                    let dst = self.gen(lhs);
+                    self.bin_imm(*op, dst, imm);
+                    return dst;
+                }
+                let (dst, src) = self.gen_pair(lhs, rhs);
+                self.bin(*op, dst, src);
+                self.x.free_reg(src);
+                dst
+            }
+            Expr::Cmp(condition, lhs, rhs) => {
+                let dst = match **rhs {
+                    Expr::Const(value) if i32::try_from(value).is_ok() => {
+                        let dst = self.gen(lhs);
+                        self.asm(format!("cmp {:?}, {}", dst, value));
+                        dst
+                    }
+                    _ => {
+                        let (dst, src) = self.gen_pair(lhs, rhs);
+                        self.x.emit(Instruction::Cmp { op1: dst, op2: src });
+                        self.x.free_reg(src);
+                        dst
+                    }
+                };
+                self.x.emit(Instruction::Setcc { cond: *condition, dst });
+                self.asm(format!("movzx {}, {}", dst.dword(), dst.byte()));
+                dst
+            }
+            Expr::Cast(inner, ty) => {
+                let dst = self.gen(inner);
+                let cast = match ty {
+                    ElemType::I8 => format!("movsx {:?}, {}", dst, dst.byte()),
+                    ElemType::I16 => format!("movsx {:?}, {}", dst, dst.word()),
+                    ElemType::I32 => format!("movsxd {:?}, {}", dst, dst.dword()),
+                    ElemType::U8 => format!("movzx {}, {}", dst.dword(), dst.byte()),
+                    ElemType::U16 => format!("movzx {}, {}", dst.dword(), dst.word()),
+                    ElemType::U32 => format!("mov {}, {}", dst.dword(), dst.dword()),
+                    ElemType::Bool => {
+                        format!("test {:?}, {:?}\nsetne {}\nmovzx {}, {}", dst, dst, dst.byte(), dst.dword(), dst.byte())
+                    }
+                    ElemType::I64 | ElemType::U64 | ElemType::F32 | ElemType::F64 => return dst,
+                };
+                for line in Box::leak(cast.into_boxed_str()).lines() {
+                    self.x.emit(Instruction::AsIs(line));
+                }
+                dst
+            }
+            Expr::Neg(inner) => {
+                let dst = self.gen(inner);
+                self.asm(format!("neg {:?}", dst));
+                dst
+            }
+            Expr::Not(inner) => {
+                let dst = self.gen(inner);
+                self.x.emit(Instruction::Not { reg: dst });
+                dst
+            }
+        }
+    }
+
+    /// evaluates both operands, the one needing more registers first so the other one does not
+    /// hold a register meanwhile
+    fn gen_pair(&mut self, lhs: &Expr, rhs: &Expr) -> (Register, Register) {
+        if rhs.registers_needed() > lhs.registers_needed() {
+            let src = self.gen(rhs);
+            let dst = self.gen(lhs);
+            (dst, src)
+        } else {
+            let dst = self.gen(lhs);
+            let src = self.gen(rhs);
+            (dst, src)
+        }
+    }
+
+    fn bin_imm(&mut self, op: BinOp, dst: Register, imm: i32) {
+        let code = match op {
+            BinOp::Add => format!("add {:?}, {}", dst, imm),
+            BinOp::Sub => format!("sub {:?}, {}", dst, imm),
+            BinOp::Mul => format!("imul {:?}, {:?}, {}", dst, dst, imm),
+            BinOp::And => format!("and {:?}, {}", dst, imm),
+            BinOp::Or => format!("or {:?}, {}", dst, imm),
+            BinOp::Xor => format!("xor {:?}, {}", dst, imm),
+            BinOp::Shl => format!("shl {:?}, {}", dst, imm),
+            BinOp::Sar => format!("sar {:?}, {}", dst, imm),
+            BinOp::Shr => format!("shr {:?}, {}", dst, imm),
+            BinOp::Div | BinOp::Rem | BinOp::UDiv | BinOp::URem => unreachable!("divisions have no immediate form"),
+        };
+        self.asm(code);
+    }
+
+    fn bin(&mut self, op: BinOp, dst: Register, src: Register) {
+        match op {
+            BinOp::Add => self.x.emit(Instruction::Add { dst, src }),
+            BinOp::Sub => self.x.emit(Instruction::Sub { dst, src }),
+            BinOp::Mul => self.asm(format!("imul {:?}, {:?}", dst, src)),
+            BinOp::And => self.x.emit(Instruction::And { dst, src }),
+            BinOp::Or => self.x.emit(Instruction::Or { dst, src }),
+            BinOp::Xor => self.x.emit(Instruction::Xor { dst, src }),
+            BinOp::Shl | BinOp::Sar | BinOp::Shr => {
+                let shift = match op {
+                    BinOp::Shl => "shl",
+                    BinOp::Sar => "sar",
+                    _ => "shr",
+                };
+                self.x.emit(Instruction::Mov { dst: Register::rcx, src });
+                self.asm(format!("{} {:?}, cl", shift, dst));
+            }
+            BinOp::Div | BinOp::Rem | BinOp::UDiv | BinOp::URem => {
+                let signed = matches!(op, BinOp::Div | BinOp::Rem);
+                self.x.emit(Instruction::Mov { dst: Register::rax, src: dst });
+                if signed {
+                    self.x.emit(Instruction::AsIs("cqo"));
+                    self.asm(format!("idiv {:?}", src));
+                } else {
+                    self.x.emit(Instruction::AsIs("xor edx, edx"));
+                    self.asm(format!("div {:?}", src));
+                }
+                let result = if matches!(op, BinOp::Div | BinOp::UDiv) { Register::rax } else { Register::rdx };
+                self.x.emit(Instruction::Mov { dst, src: result });
+            }
+        }
+    }
+}
+
+impl From<i64> for Expr {
+    fn from(value: i64) -> Self {
+        Expr::Const(value)
+    }
+}
+
+impl From<Register> for Expr {
+    fn from(reg: Register) -> Self {
+        Expr::Reg(reg)
+    }
+}
+
+macro_rules! expr_op {
+    ($trait:ident, $method:ident, $op:expr) => {
+        impl<T: Into<Expr>> ops::$trait<T> for Expr {
+            type Output = Expr;
+            fn $method(self, rhs: T) -> Expr {
+                Expr::bin($op, self, rhs.into())
+            }
+        }
+    };
+}
+
+expr_op!(Add, add, BinOp::Add);
+expr_op!(Sub, sub, BinOp::Sub);
+expr_op!(Mul, mul, BinOp::Mul);
+expr_op!(Div, div, BinOp::Div);
+expr_op!(Rem, rem, BinOp::Rem);
+expr_op!(BitAnd, bitand, BinOp::And);
+expr_op!(BitOr, bitor, BinOp::Or);
+expr_op!(BitXor, bitxor, BinOp::Xor);
+expr_op!(Shl, shl, BinOp::Shl);
+expr_op!(Shr, shr, BinOp::Sar);
+
+impl ops::Neg for Expr {
+    type Output = Expr;
+    fn neg(self) -> Expr {
+        Expr::Neg(Box::new(self))
+    }
+}
+
+impl ops::Not for Expr {
+    type Output = Expr;
+    fn not(self) -> Expr {
+        Expr::Not(Box::new(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folded(expr: Expr) -> i64 {
+        match expr.fold() {
+            Expr::Const(value) => value,
+            other => panic!("{:?} did not fold to a constant", other),
+        }
+    }
+
+    #[test]
+    fn arithmetic_wraps() {
+        assert_eq!(folded(Expr::Const(i64::MAX) + 1), i64::MIN);
+        assert_eq!(folded(Expr::Const(i64::MIN) - 1), i64::MAX);
+        assert_eq!(folded(Expr::Const(i64::MIN) / -1), i64::MIN);
+        assert_eq!(folded(Expr::bin(BinOp::Rem, Expr::Const(i64::MIN), Expr::Const(-1))), 0);
+        assert_eq!(folded(-Expr::Const(i64::MIN)), i64::MIN);
+    }
+
+    #[test]
+    fn division_rounds_toward_zero() {
+        assert_eq!(folded(Expr::Const(-7) / 2), -3);
+        assert_eq!(folded(Expr::Const(-7) % 2), -1);
+        assert_eq!(folded(Expr::Const(-1).udiv(Expr::Const(2))), i64::MAX);
+        assert_eq!(folded(Expr::Const(-1).urem(Expr::Const(10))), (u64::MAX % 10) as i64);
+    }
+
+    #[test]
+    #[should_panic(expected = "division by zero")]
+    fn constant_division_by_zero_panics() {
+        (Expr::Const(1) / 0).fold();
+    }
+
+    #[test]
+    fn shift_counts_are_masked() {
+        assert_eq!(folded(Expr::Const(1) << 65), 2);
+        assert_eq!(folded(Expr::Const(1) << -1), i64::MIN);
+        assert_eq!(folded(Expr::Const(-8) >> 65), -4);
+        assert_eq!(folded(Expr::Const(-1).ushr(Expr::Const(124))), 15);
+    }
+
+    #[test]
+    fn compares_and_casts() {
+        assert_eq!(folded(Expr::cmp(Condition::Less, Expr::Const(-1), Expr::Const(1))), 1);
+        assert_eq!(folded(Expr::cmp(Condition::Below, Expr::Const(-1), Expr::Const(1))), 0);
+        assert_eq!(folded(Expr::cmp(Condition::Above, Expr::Const(-1), Expr::Const(1))), 1);
+        assert_eq!(folded(Expr::Const(300).cast(ElemType::I8)), 44);
+        assert_eq!(folded(Expr::Const(-1).cast(ElemType::U8)), 255);
+        assert_eq!(folded(Expr::Const(-1).cast(ElemType::U32)), u32::MAX as i64);
+        assert_eq!(folded(Expr::Const(256).cast(ElemType::Bool)), 1);
+        assert_eq!(folded(!(Expr::Const(0) ^ 5)), -6);
+    }
+
+    #[test]
+    fn identities_and_operand_order() {
+        let x = || Expr::var("x");
+        assert_eq!((x() + 0).fold(), x());
+        assert_eq!((Expr::Const(0) + x()).fold(), x());
+        assert_eq!((x() * 1).fold(), x());
+        assert_eq!((x() & -1).fold(), x());
+        assert_eq!((x() << 0).fold(), x());
+        assert_eq!(Expr::bin(BinOp::Mul, x(), Expr::Const(0)).fold(), Expr::Const(0));
+        assert_eq!((Expr::Const(2) + 3).fold(), Expr::Const(5));
+        assert_eq!(((Expr::Const(2) + 3) * x()).fold(), Expr::bin(BinOp::Mul, x(), Expr::Const(5)));
+        assert_eq!((Expr::Const(3) - x()).fold(), Expr::bin(BinOp::Sub, Expr::Const(3), x()));
+        assert_eq!((x() / 1).fold(), x());
+        let rem = Expr::bin(BinOp::Rem, x(), Expr::Const(1));
+        assert_eq!(rem.fold(), rem);
+    }
+
+    #[test]
+    fn sethi_ullman_numbers() {
+        let v = Expr::var;
+        assert_eq!((v("a") + 1).registers_needed(), 1);
+        assert_eq!((v("a") + v("b")).registers_needed(), 2);
+        assert_eq!(((v("a") + v("b")) * (v("c") + v("d"))).registers_needed(), 3);
+        assert_eq!(((v("a") + v("b")) * v("c")).registers_needed(), 2);
+        assert_eq!((v("a") / 3).registers_needed(), 2);
+    }
+}
@@ -0,0 +1,312 @@
+use std::ops::{Deref, DerefMut};
+use crate::{
+    init::{Funcs, LinuxX8664, Register},
+    instructions::{Condition, Instruction},
+};
+
+/// something instructions can be emitted into, the program itself or the body of a function
+pub trait Emit {
+    fn emit(&mut self, instr: Instruction);
+    /// a number for labels that is unique in the whole program
+    fn next_label_id(&mut self) -> usize;
+    /// takes ``reg`` from the register allocator of the program, like ``LinuxX8664::get_reg``
+    fn get_reg(&mut self, reg: Register, force: bool) -> Register;
+    fn free_reg(&mut self, reg: Register);
+}
+
+impl Emit for LinuxX8664 {
+    fn emit(&mut self, instr: Instruction) {
+        LinuxX8664::emit(self, instr);
+    }
+    fn next_label_id(&mut self) -> usize {
+        LinuxX8664::next_label_id(self)
+    }
+    fn get_reg(&mut self, reg: Register, force: bool) -> Register {
+        LinuxX8664::get_reg(self, reg, force)
+    }
+    fn free_reg(&mut self, reg: Register) {
+        LinuxX8664::free_reg(self, reg);
+    }
+}
+
+/// the body of a function that is not added yet, its labels are numbered by the program it belongs to.
+/// ``add`` hands the function to the program when it is done
+pub struct FuncBody<'x> {
+    parent: &'x mut LinuxX8664,
+    func: Funcs,
+}
+
+impl<'x> FuncBody<'x> {
+    pub fn new(parent: &'x mut LinuxX8664, name: &'static str, args: Vec<Register>) -> Self {
+        Self { parent, func: Funcs::new(name, args, Vec::new()) }
+    }
+    /// adds the function to the program
+    pub fn add(self) {
+        self.parent.add_func(self.func);
+    }
+}
+
+impl Emit for FuncBody<'_> {
+    fn emit(&mut self, instr: Instruction) {
+        self.func.body.push(instr);
+    }
+    fn next_label_id(&mut self) -> usize {
+        self.parent.next_label_id()
+    }
+    fn get_reg(&mut self, reg: Register, force: bool) -> Register {
+        self.parent.get_reg(reg, force)
+    }
+    fn free_reg(&mut self, reg: Register) {
+        self.parent.free_reg(reg);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Rhs {
+    Reg(Register),
+    Imm(i32),
+    /// ``test lhs, lhs``
+    Itself,
+    /// the flags are already set, nothing is compared
+    Flags,
+}
+
+/// a condition for the control flow builders, compared right before the jump
+#[derive(Debug, Clone, Copy)]
+pub struct Cond {
+    lhs: Register,
+    rhs: Rhs,
+    condition: Condition,
+}
+
+impl Cond {
+    /// ``cmp lhs, rhs``, holds when ``lhs`` relates to ``rhs`` as ``condition`` says
+    pub fn cmp(lhs: Register, condition: Condition, rhs: Register) -> Self {
+        Self { lhs, rhs: Rhs::Reg(rhs), condition }
+    }
+    /// ``cmp lhs, imm``
+    pub fn cmp_imm(lhs: Register, condition: Condition, imm: i32) -> Self {
+        Self { lhs, rhs: Rhs::Imm(imm), condition }
+    }
+    /// holds when ``reg`` is 0
+    pub fn zero(reg: Register) -> Self {
+        Self { lhs: reg, rhs: Rhs::Itself, condition: Condition::Equal }
+    }
+    /// holds when ``reg`` is not 0
+    pub fn nonzero(reg: Register) -> Self {
+        Self { lhs: reg, rhs: Rhs::Itself, condition: Condition::NotEqual }
+    }
+    /// tests the flags left by the previous instruction
+    pub fn flags(condition: Condition) -> Self {
+        Self { lhs: Register::rax, rhs: Rhs::Flags, condition }
+    }
+    /// the same comparison with the negated condition
+    pub fn negate(self) -> Self {
+        Self { condition: self.condition.negate(), ..self }
+    }
+
+    /// emits the comparison and a jump to ``label`` taken when the condition holds
+    fn jump_if<E: Emit + ?Sized>(&self, target: &mut E, label: &str) {
+        let compare = match self.rhs {
+            Rhs::Reg(rhs) => Some(format!("cmp {:?}, {:?}", self.lhs, rhs)),
+            Rhs::Imm(imm) => Some(format!("cmp {:?}, {}", self.lhs, imm)),
+            Rhs::Itself => Some(format!("test {:?}, {:?}", self.lhs, self.lhs)),
+            Rhs::Flags => None,
+        };
+        if let Some(compare) = compare {
+            target.emit(Instruction::AsIs(Box::leak(compare.into_boxed_str())));
+        }
+        target.emit(Instruction::Jcc { cond: self.condition, label: label.to_string() });
+    }
+}
+
+/// the code inside a builder, usable like the program or function it emits into.
+/// inside a loop (also in nested ifs) ``break_`` and ``continue_`` jump out of or back to the innermost loop
+pub struct Block<'e, E: Emit> {
+    target: &'e mut E,
+    /// break and continue labels of the innermost loop
+    loop_labels: Option<(String, String)>,
+}
+
+impl<E: Emit> Deref for Block<'_, E> {
+    type Target = E;
+    fn deref(&self) -> &E {
+        self.target
+    }
+}
+
+impl<E: Emit> DerefMut for Block<'_, E> {
+    fn deref_mut(&mut self) -> &mut E {
+        self.target
+    }
+}
+
+impl<E: Emit> Emit for Block<'_, E> {
+    fn emit(&mut self, instr: Instruction) {
+        self.target.emit(instr);
+    }
+    fn next_label_id(&mut self) -> usize {
+        self.target.next_label_id()
+    }
+    fn get_reg(&mut self, reg: Register, force: bool) -> Register {
+        self.target.get_reg(reg, force)
+    }
+    fn free_reg(&mut self, reg: Register) {
+        self.target.free_reg(reg);
+    }
+}
+
+fn new_label<E: Emit + ?Sized>(target: &mut E, kind: &str) -> (usize, String) {
+    let id = target.next_label_id();
+    (id, format!("{}_{}", kind, id))
+}
+
+impl<'e, E: Emit> Block<'e, E> {
+    /// a builder on ``target`` outside of any loop
+    pub fn new(target: &'e mut E) -> Self {
+        Self { target, loop_labels: None }
+    }
+
+    fn inner(&mut self, loop_labels: Option<(String, String)>) -> Block<'_, E> {
+        Block { target: &mut *self.target, loop_labels }
+    }
+
+    /// runs ``then`` when ``cond`` holds, chain ``.else_`` for the other case
+    pub fn if_<F: FnOnce(&mut Block<'_, E>)>(&mut self, cond: Cond, then: F) -> If<'_, E> {
+        let (_, else_label) = new_label(self.target, "if_else");
+        cond.negate().jump_if(self.target, &else_label);
+        let labels = self.loop_labels.clone();
+        let mut block = self.inner(labels);
+        then(&mut block);
+        If { block, else_label, open: true }
+    }
+
+    /// runs ``body`` as long as ``cond`` holds, checking it before every iteration
+    pub fn while_<F: FnOnce(&mut Block<'_, E>)>(&mut self, cond: Cond, body: F) {
+        let (id, top) = new_label(self.target, "while");
+        let end = format!("while_{}_end", id);
+        self.emit(Instruction::Label(top.clone()));
+        cond.negate().jump_if(self.target, &end);
+        body(&mut self.inner(Some((end.clone(), top.clone()))));
+        self.emit(Instruction::Jmp(top));
+        self.emit(Instruction::Label(end));
+    }
+
+    /// runs ``body`` until it calls ``break_``
+    pub fn loop_<F: FnOnce(&mut Block<'_, E>)>(&mut self, body: F) {
+        let (id, top) = new_label(self.target, "loop");
+        let end = format!("loop_{}_end", id);
+        self.emit(Instruction::Label(top.clone()));
+        body(&mut self.inner(Some((end.clone(), top.clone()))));
+        self.emit(Instruction::Jmp(top));
+        self.emit(Instruction::Label(end));
+    }
+
+    /// runs ``body`` with ``counter`` going from ``start`` up to ``end`` (exclusive, signed).
+    /// ``continue_`` moves on to the next value. ``counter`` is taken from the register allocator
+    /// until the loop ends, it must not be in use already and the body must leave it alone
+    pub fn for_<F: FnOnce(&mut Block<'_, E>)>(&mut self, counter: Register, start: i64, end: i32, body: F) {
+        let reserved = self.target.get_reg(counter, false);
+        if reserved != counter {
+            self.target.free_reg(reserved);
+            panic!("for_: the counter {:?} is already in use", counter);
+        }
+        let (id, top) = new_label(self.target, "for");
+        let next = format!("for_{}_next", id);
+        let done = format!("for_{}_end", id);
+        self.emit(Instruction::MovImm { dst: counter, imm: start });
+        self.emit(Instruction::Label(top.clone()));
+        Cond::cmp_imm(counter, Condition::GreaterEqual, end).jump_if(self.target, &done);
+        body(&mut self.inner(Some((done.clone(), next.clone()))));
+        self.emit(Instruction::Label(next));
+        self.emit(Instruction::AddImm { dst: counter, imm: 1 });
+        self.emit(Instruction::Jmp(top));
+        self.emit(Instruction::Label(done));
+        self.target.free_reg(counter);
+    }
+
+    /// leaves the innermost loop
+    pub fn break_(&mut self) {
+        match &self.loop_labels {
+            Some((end, _)) => {
+                let end = end.clone();
+                self.emit(Instruction::Jmp(end));
+            }
+            None => panic!("break_: not inside a loop"),
+        }
+    }
+
+    /// leaves the innermost loop when ``cond`` holds
+    pub fn break_if(&mut self, cond: Cond) {
+        match &self.loop_labels {
+            Some((end, _)) => cond.jump_if(self.target, end),
+            None => panic!("break_if: not inside a loop"),
+        }
+    }
+
+    /// starts the next iteration of the innermost loop
+    pub fn continue_(&mut self) {
+        match &self.loop_labels {
+            Some((_, next)) => {
+                let next = next.clone();
+                self.emit(Instruction::Jmp(next));
+            }
+            None => panic!("continue_: not inside a loop"),
+        }
+    }
+}
+
+/// an ``if_`` whose body was emitted, ``else_`` adds the other case. dropping it ends the if
+pub struct If<'e, E: Emit> {
+    block: Block<'e, E>,
+    else_label: String,
+    open: bool,
+}
+
+impl<E: Emit> If<'_, E> {
+    /// runs ``body`` when the condition of the ``if_`` does not hold
+    pub fn else_<F: FnOnce(&mut Block<'_, E>)>(mut self, body: F) {
+        let end = format!("{}_end", self.else_label);
+        self.block.emit(Instruction::Jmp(end.clone()));
+        self.block.emit(Instruction::Label(self.else_label.clone()));
+        body(&mut self.block);
+        self.block.emit(Instruction::Label(end));
+        self.open = false;
+    }
+}
+
+impl<E: Emit> Drop for If<'_, E> {
+    fn drop(&mut self) {
+        if self.open {
+            let label = self.else_label.clone();
+            self.block.emit(Instruction::Label(label));
+        }
+    }
+}
+
+/// the control flow builders on anything instructions can be emitted into
+pub trait ControlFlow: Emit + Sized {
+    /// runs ``then`` when ``cond`` holds, chain ``.else_`` for the other case
+    fn if_<F: FnOnce(&mut Block<'_, Self>)>(&mut self, cond: Cond, then: F) -> If<'_, Self> {
+        let (_, else_label) = new_label(self, "if_else");
+        cond.negate().jump_if(self, &else_label);
+        let mut block = Block::new(self);
+        then(&mut block);
+        If { block, else_label, open: true }
+    }
+    /// runs ``body`` as long as ``cond`` holds
+    fn while_<F: FnOnce(&mut Block<'_, Self>)>(&mut self, cond: Cond, body: F) {
+        Block::new(self).while_(cond, body);
+    }
+    /// runs ``body`` until it calls ``break_``
+    fn loop_<F: FnOnce(&mut Block<'_, Self>)>(&mut self, body: F) {
+        Block::new(self).loop_(body);
+    }
+    /// runs ``body`` with ``counter`` going from ``start`` up to ``end`` (exclusive, signed),
+    /// ``counter`` is reserved in the register allocator until the loop ends
+    fn for_<F: FnOnce(&mut Block<'_, Self>)>(&mut self, counter: Register, start: i64, end: i32, body: F) {
+        Block::new(self).for_(counter, start, end, body);
+    }
+}
+
+impl<E: Emit> ControlFlow for E {}
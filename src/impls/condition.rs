@@ -0,0 +1,136 @@
+use crate::instructions::Condition;
+
+impl Condition {
+    /// the condition that holds exactly when this one does not
+    pub fn negate(&self) -> Condition {
+        match self {
+            Condition::Equal => Condition::NotEqual,
+            Condition::NotEqual => Condition::Equal,
+            Condition::Less => Condition::GreaterEqual,
+            Condition::LessEqual => Condition::Greater,
+            Condition::Greater => Condition::LessEqual,
+            Condition::GreaterEqual => Condition::Less,
+            Condition::Below => Condition::AboveEqual,
+            Condition::BelowEqual => Condition::Above,
+            Condition::Above => Condition::BelowEqual,
+            Condition::AboveEqual => Condition::Below,
+            Condition::Zero => Condition::NotZero,
+            Condition::NotZero => Condition::Zero,
+            Condition::Sign => Condition::NotSign,
+            Condition::NotSign => Condition::Sign,
+            Condition::Overflow => Condition::NotOverflow,
+            Condition::NotOverflow => Condition::Overflow,
+            Condition::Carry => Condition::NotCarry,
+            Condition::NotCarry => Condition::Carry,
+            Condition::Parity => Condition::NotParity,
+            Condition::NotParity => Condition::Parity,
+        }
+    }
+    /// whether the condition holds after ``cmp a, b``
+    pub fn holds(&self, a: i64, b: i64) -> bool {
+        let (ua, ub) = (a as u64, b as u64);
+        let diff = a.wrapping_sub(b);
+        match self {
+            Condition::Equal | Condition::Zero => a == b,
+            Condition::NotEqual | Condition::NotZero => a != b,
+            Condition::Less => a < b,
+            Condition::LessEqual => a <= b,
+            Condition::Greater => a > b,
+            Condition::GreaterEqual => a >= b,
+            Condition::Below | Condition::Carry => ua < ub,
+            Condition::BelowEqual => ua <= ub,
+            Condition::Above => ua > ub,
+            Condition::AboveEqual | Condition::NotCarry => ua >= ub,
+            Condition::Sign => diff < 0,
+            Condition::NotSign => diff >= 0,
+            Condition::Overflow => a.checked_sub(b).is_none(),
+            Condition::NotOverflow => a.checked_sub(b).is_some(),
+            Condition::Parity => (diff as u8).count_ones().is_multiple_of(2),
+            Condition::NotParity => !(diff as u8).count_ones().is_multiple_of(2),
+        }
+    }
+    /// the condition code as used in ``jcc``/``setcc``/``cmovcc`` mnemonics
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Condition::Equal => "e",
+            Condition::NotEqual => "ne",
+            Condition::Less => "l",
+            Condition::LessEqual => "le",
+            Condition::Greater => "g",
+            Condition::GreaterEqual => "ge",
+            Condition::Below => "b",
+            Condition::BelowEqual => "be",
+            Condition::Above => "a",
+            Condition::AboveEqual => "ae",
+            Condition::Zero => "z",
+            Condition::NotZero => "nz",
+            Condition::Sign => "s",
+            Condition::NotSign => "ns",
+            Condition::Overflow => "o",
+            Condition::NotOverflow => "no",
+            Condition::Carry => "c",
+            Condition::NotCarry => "nc",
+            Condition::Parity => "p",
+            Condition::NotParity => "np",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::instructions::Condition::{self, *};
+
+    const ALL: [Condition; 20] = [
+        Equal, NotEqual, Less, LessEqual, Greater, GreaterEqual, Below, BelowEqual, Above, AboveEqual,
+        Zero, NotZero, Sign, NotSign, Overflow, NotOverflow, Carry, NotCarry, Parity, NotParity,
+    ];
+    const VALUES: [i64; 9] = [i64::MIN, i64::MIN + 1, -256, -1, 0, 1, 3, 255, i64::MAX];
+
+    #[test]
+    fn negate_is_the_opposite() {
+        for cond in ALL {
+            assert_eq!(cond.negate().negate(), cond);
+            for a in VALUES {
+                for b in VALUES {
+                    assert_ne!(cond.holds(a, b), cond.negate().holds(a, b), "{:?} {} {}", cond, a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn signed_and_unsigned() {
+        assert!(Less.holds(-1, 1) && !Below.holds(-1, 1));
+        assert!(Above.holds(-1, 1) && !Greater.holds(-1, 1));
+        assert!(BelowEqual.holds(0, -1) && GreaterEqual.holds(0, -1));
+        assert!(Carry.holds(1, 2) && !Carry.holds(-1, 2));
+        assert!(Zero.holds(7, 7) && NotZero.holds(7, 8));
+    }
+
+    #[test]
+    fn sign_and_overflow() {
+        assert!(Sign.holds(1, 2));
+        // the subtraction wraps: SF is clear but OF is set, which is how ``Less`` still holds
+        assert!(!Sign.holds(i64::MIN, 1) && Overflow.holds(i64::MIN, 1) && Less.holds(i64::MIN, 1));
+        assert!(Overflow.holds(i64::MAX, -1));
+        assert!(Overflow.holds(0, i64::MIN));
+        assert!(NotOverflow.holds(-1, i64::MIN));
+        assert!(NotOverflow.holds(5, 3));
+        // SF != OF is exactly the signed less than
+        for a in VALUES {
+            for b in VALUES {
+                assert_eq!(Sign.holds(a, b) != Overflow.holds(a, b), Less.holds(a, b), "{} {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn parity_reads_the_low_byte() {
+        assert!(Parity.holds(3, 0));
+        assert!(NotParity.holds(1, 0));
+        assert!(Parity.holds(0x100, 0));
+        assert!(Parity.holds(0, 0));
+        assert!(Parity.holds(0, 1)); // 0xff, eight bits
+        assert!(NotParity.holds(0x107, 0));
+    }
+}
@@ -1 +1,18 @@
-pub mod variables;
\ No newline at end of file
+pub mod condition;
+pub mod register;
+pub mod variables;
+pub mod xstd_alloc;
+pub mod xstd_args;
+pub mod xstd_array;
+pub mod xstd_file;
+pub mod xstd_fmt;
+pub mod xstd_in;
+pub mod xstd_mem;
+pub mod xstd_out;
+pub mod xstd_panic;
+pub mod xstd_proc;
+pub mod xstd_signal;
+pub mod xstd_struct;
+pub mod xstd_switch;
+pub mod xstd_thread;
+pub mod xstd_time;
@@ -0,0 +1,93 @@
+use crate::init::Register;
+
+impl Register {
+    /// name of the low 32 bits of the register
+    pub fn dword(&self) -> &'static str {
+        match self {
+            Register::rax => "eax",
+            Register::rbx => "ebx",
+            Register::rcx => "ecx",
+            Register::rdx => "edx",
+            Register::rsi => "esi",
+            Register::rdi => "edi",
+            Register::rbp => "ebp",
+            Register::rsp => "esp",
+            Register::r8 => "r8d",
+            Register::r9 => "r9d",
+            Register::r10 => "r10d",
+            Register::r11 => "r11d",
+            Register::r12 => "r12d",
+            Register::r13 => "r13d",
+            Register::r14 => "r14d",
+            Register::r15 => "r15d",
+        }
+    }
+    /// name of the low 16 bits of the register
+    pub fn word(&self) -> &'static str {
+        match self {
+            Register::rax => "ax",
+            Register::rbx => "bx",
+            Register::rcx => "cx",
+            Register::rdx => "dx",
+            Register::rsi => "si",
+            Register::rdi => "di",
+            Register::rbp => "bp",
+            Register::rsp => "sp",
+            Register::r8 => "r8w",
+            Register::r9 => "r9w",
+            Register::r10 => "r10w",
+            Register::r11 => "r11w",
+            Register::r12 => "r12w",
+            Register::r13 => "r13w",
+            Register::r14 => "r14w",
+            Register::r15 => "r15w",
+        }
+    }
+    /// name of the low 8 bits of the register
+    pub fn byte(&self) -> &'static str {
+        match self {
+            Register::rax => "al",
+            Register::rbx => "bl",
+            Register::rcx => "cl",
+            Register::rdx => "dl",
+            Register::rsi => "sil",
+            Register::rdi => "dil",
+            Register::rbp => "bpl",
+            Register::rsp => "spl",
+            Register::r8 => "r8b",
+            Register::r9 => "r9b",
+            Register::r10 => "r10b",
+            Register::r11 => "r11b",
+            Register::r12 => "r12b",
+            Register::r13 => "r13b",
+            Register::r14 => "r14b",
+            Register::r15 => "r15b",
+        }
+    }
+    /// name of the register when it holds a value of the given size in bytes
+    pub fn sized(&self, size: usize) -> &'static str {
+        match size {
+            1 => self.byte(),
+            2 => self.word(),
+            4 => self.dword(),
+            _ => match self {
+                Register::rax => "rax",
+                Register::rbx => "rbx",
+                Register::rcx => "rcx",
+                Register::rdx => "rdx",
+                Register::rsi => "rsi",
+                Register::rdi => "rdi",
+                Register::rbp => "rbp",
+                Register::rsp => "rsp",
+                Register::r8 => "r8",
+                Register::r9 => "r9",
+                Register::r10 => "r10",
+                Register::r11 => "r11",
+                Register::r12 => "r12",
+                Register::r13 => "r13",
+                Register::r14 => "r14",
+                Register::r15 => "r15",
+            },
+        }
+    }
+}
@@ -1,43 +1,212 @@
-use crate::init::Variables;
+use crate::init::{ArrayInit, ElemType, StructLayout, Variables};
 
 
 impl Variables{
     pub fn match_var(&self,to_match_with:&Variables) -> bool{
-
         match (self,to_match_with){
-            (Variables::I8(_), Variables::I8(_)) => return true,
-            (Variables::I16(_), Variables::I16(_)) => return true,
-            (Variables::I32(_), Variables::I32(_)) => return true,
-            (Variables::I64(_), Variables::I64(_)) => return true,
-            (Variables::U8(_), Variables::U8(_)) => return true,
-            (Variables::U16(_), Variables::U16(_)) => return true,
-            (Variables::U32(_), Variables::U32(_)) => return true,
-            (Variables::U64(_), Variables::U64(_)) => return true,
-            (Variables::F32(_), Variables::F32(_)) => return true,
-            (Variables::F64(_), Variables::F64(_)) => return true,
-            (Variables::Bool(_), Variables::Bool(_)) => return true,
-            (Variables::Str(_), Variables::Str(_)) => return true,
-            (Variables::AsIs(_), Variables::AsIs(_)) => return true,
+            (Variables::I8(_), Variables::I8(_)) => true,
+            (Variables::I16(_), Variables::I16(_)) => true,
+            (Variables::I32(_), Variables::I32(_)) => true,
+            (Variables::I64(_), Variables::I64(_)) => true,
+            (Variables::U8(_), Variables::U8(_)) => true,
+            (Variables::U16(_), Variables::U16(_)) => true,
+            (Variables::U32(_), Variables::U32(_)) => true,
+            (Variables::U64(_), Variables::U64(_)) => true,
+            (Variables::F32(_), Variables::F32(_)) => true,
+            (Variables::F64(_), Variables::F64(_)) => true,
+            (Variables::Bool(_), Variables::Bool(_)) => true,
+            (Variables::Str(_), Variables::Str(_)) => true,
+            (Variables::Bytes(_), Variables::Bytes(_)) => true,
+            (Variables::Ptr { .. }, Variables::Ptr { .. }) => true,
+            (Variables::AsIs(_), Variables::AsIs(_)) => true,
+            (Variables::Array { elem: a, .. }, Variables::Array { elem: b, .. }) => a == b,
+            (Variables::Struct { layout: a, .. }, Variables::Struct { layout: b, .. }) => std::ptr::eq(*a, *b),
             _ => false,
-        };
-
-        true
+        }
     }
     pub fn get_value(&self) -> String{
         match self{
-            Variables::I8(val) => return val.to_string(),
-            Variables::I16(val) => return val.to_string(),
-            Variables::I32(val) => return val.to_string(),
-            Variables::I64(val) => return val.to_string(),
-            Variables::U8(val) => return val.to_string(),
-            Variables::U16(val) => return val.to_string(),
-            Variables::U32(val) => return val.to_string(),
-            Variables::U64(val) => return val.to_string(),
-            Variables::F32(val) => return val.to_string(),
-            Variables::F64(val) => return val.to_string(),
-            Variables::Bool(val) => return val.to_string(),
-            Variables::Str(val) => return val.to_string(),
-            Variables::AsIs(val) => return val.to_string(),
-        }
-    }
-}
\ No newline at end of file
+            Variables::I8(val) => val.to_string(),
+            Variables::I16(val) => val.to_string(),
+            Variables::I32(val) => val.to_string(),
+            Variables::I64(val) => val.to_string(),
+            Variables::U8(val) => val.to_string(),
+            Variables::U16(val) => val.to_string(),
+            Variables::U32(val) => val.to_string(),
+            Variables::U64(val) => val.to_string(),
+            Variables::F32(val) => val.to_string(),
+            Variables::F64(val) => val.to_string(),
+            Variables::Bool(val) => val.to_string(),
+            Variables::Str(val) => val.to_string(),
+            Variables::Bytes(val) => val.escape_ascii().to_string(),
+            Variables::Ptr { target, offset: 0 } => target.to_string(),
+            Variables::Ptr { target, offset } => format!("{} + {}", target, offset),
+            Variables::AsIs(val) => val.to_string(),
+            Variables::Array { elem, len, .. } => format!("[{:?}; {}]", elem, len),
+            Variables::Struct { layout, .. } => layout.name.to_string(),
+        }
+    }
+    /// size in bytes of the variable as laid out by the asm makers (``Str`` includes the trailing 0)
+    pub fn size(&self) -> usize {
+        match self {
+            Variables::I8(_) | Variables::U8(_) | Variables::Bool(_) => 1,
+            Variables::I16(_) | Variables::U16(_) => 2,
+            Variables::I32(_) | Variables::U32(_) | Variables::F32(_) => 4,
+            Variables::I64(_) | Variables::U64(_) | Variables::F64(_) | Variables::Ptr { .. } => 8,
+            Variables::Str(val) => val.len() + 1,
+            Variables::Bytes(val) => val.len(),
+            Variables::AsIs(_) => 0,
+            Variables::Array { elem, len, .. } => elem.size() * len,
+            Variables::Struct { layout, .. } => layout.size(),
+        }
+    }
+    /// alignment in bytes the System V ABI gives the variable, text and ``AsIs`` are byte aligned
+    pub fn align(&self) -> usize {
+        match self {
+            Variables::Str(_) | Variables::Bytes(_) | Variables::AsIs(_) => 1,
+            Variables::Array { elem, .. } => elem.size(),
+            Variables::Struct { layout, .. } => layout.align(),
+            _ => self.size(),
+        }
+    }
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            Variables::I8(_)
+                | Variables::I16(_)
+                | Variables::I32(_)
+                | Variables::I64(_)
+                | Variables::U8(_)
+                | Variables::U16(_)
+                | Variables::U32(_)
+                | Variables::U64(_)
+        )
+    }
+    /// ``Str`` or an array of bytes, which can hold 0 terminated text
+    pub fn is_text(&self) -> bool {
+        matches!(
+            self,
+            Variables::Str(_) | Variables::Array { elem: ElemType::U8 | ElemType::I8, .. }
+        )
+    }
+    /// symbols the value points to, through ``Ptr``, ``ArrayInit::Ptrs`` or the fields of a struct
+    pub fn targets(&self) -> Vec<&'static str> {
+        match *self {
+            Variables::Ptr { target, .. } => vec![target],
+            Variables::Array { init: ArrayInit::Ptrs(targets), .. } => targets.to_vec(),
+            Variables::Struct { init, .. } if !init.is_empty() => init.iter().flat_map(|var| var.targets()).collect(),
+            Variables::Struct { layout, .. } => layout.fields.iter().flat_map(|(_, var)| var.targets()).collect(),
+            _ => Vec::new(),
+        }
+    }
+    /// the bytes of a scalar as an integer, floats as their bit pattern. 0 for everything else
+    pub fn bits(&self) -> u64 {
+        match *self {
+            Variables::I8(val) => val as u8 as u64,
+            Variables::I16(val) => val as u16 as u64,
+            Variables::I32(val) => val as u32 as u64,
+            Variables::I64(val) => val as u64,
+            Variables::U8(val) => val as u64,
+            Variables::U16(val) => val as u64,
+            Variables::U32(val) => val as u64,
+            Variables::U64(val) => val,
+            Variables::F32(val) => val.to_bits() as u64,
+            Variables::F64(val) => val.to_bits(),
+            Variables::Bool(val) => val as u64,
+            _ => 0,
+        }
+    }
+    /// the unsigned integer type of the same width for signed integers, anything else as it is
+    pub fn to_unsigned(&self) -> Variables {
+        match *self {
+            Variables::I8(val) => Variables::U8(val as u8),
+            Variables::I16(val) => Variables::U16(val as u16),
+            Variables::I32(val) => Variables::U32(val as u32),
+            Variables::I64(val) => Variables::U64(val as u64),
+            other => other,
+        }
+    }
+    pub fn is_signed(&self) -> bool {
+        matches!(self, Variables::I8(_) | Variables::I16(_) | Variables::I32(_) | Variables::I64(_))
+    }
+}
+
+impl ElemType {
+    pub fn size(&self) -> usize {
+        match self {
+            ElemType::I8 | ElemType::U8 | ElemType::Bool => 1,
+            ElemType::I16 | ElemType::U16 => 2,
+            ElemType::I32 | ElemType::U32 | ElemType::F32 => 4,
+            ElemType::I64 | ElemType::U64 | ElemType::F64 => 8,
+        }
+    }
+    pub fn is_float(&self) -> bool {
+        matches!(self, ElemType::F32 | ElemType::F64)
+    }
+    /// a scalar variable of this type holding 0, for code that works on scalars
+    pub fn scalar(&self) -> Variables {
+        match self {
+            ElemType::I8 => Variables::I8(0),
+            ElemType::I16 => Variables::I16(0),
+            ElemType::I32 => Variables::I32(0),
+            ElemType::I64 => Variables::I64(0),
+            ElemType::U8 => Variables::U8(0),
+            ElemType::U16 => Variables::U16(0),
+            ElemType::U32 => Variables::U32(0),
+            ElemType::U64 => Variables::U64(0),
+            ElemType::F32 => Variables::F32(0.0),
+            ElemType::F64 => Variables::F64(0.0),
+            ElemType::Bool => Variables::Bool(false),
+        }
+    }
+    /// nasm data directive (``db``, ``dw``, ``dd`` or ``dq``) for one element
+    pub fn data_directive(&self) -> &'static str {
+        ["db", "dw", "dd", "dq"][self.size().trailing_zeros() as usize]
+    }
+    /// nasm ``.bss`` directive (``resb``, ``resw``, ``resd`` or ``resq``) for one element
+    pub fn bss_directive(&self) -> &'static str {
+        ["resb", "resw", "resd", "resq"][self.size().trailing_zeros() as usize]
+    }
+}
+
+impl StructLayout {
+    /// name, offset and type of every field in order
+    pub fn offsets(&self) -> Vec<(&'static str, usize, Variables)> {
+        let mut end: usize = 0;
+        self.fields
+            .iter()
+            .map(|(name, kind)| {
+                if let Variables::AsIs(_) = kind {
+                    panic!("struct `{}`: field `{}` can not be AsIs, its size is unknown", self.name, name);
+                }
+                let offset = end.next_multiple_of(kind.align());
+                end = offset + kind.size();
+                (*name, offset, *kind)
+            })
+            .collect()
+    }
+    /// the largest field alignment, 1 for an empty struct
+    pub fn align(&self) -> usize {
+        self.fields.iter().map(|(_, kind)| kind.align()).max().unwrap_or(1)
+    }
+    /// size in bytes including the padding after the last field
+    pub fn size(&self) -> usize {
+        let end = self.offsets().last().map_or(0, |(_, offset, kind)| offset + kind.size());
+        end.next_multiple_of(self.align())
+    }
+    /// offset and type of ``path``, a field name or a ``.`` separated path into nested structs
+    pub fn field(&self, path: &str) -> Option<(usize, Variables)> {
+        let (name, rest) = match path.split_once('.') {
+            Some((name, rest)) => (name, Some(rest)),
+            None => (path, None),
+        };
+        let (_, offset, kind) = self.offsets().into_iter().find(|(field, ..)| *field == name)?;
+        match (rest, kind) {
+            (None, _) => Some((offset, kind)),
+            (Some(rest), Variables::Struct { layout, .. }) => {
+                layout.field(rest).map(|(inner, kind)| (offset + inner, kind))
+            }
+            _ => None,
+        }
+    }
+}
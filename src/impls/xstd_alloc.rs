@@ -0,0 +1,182 @@
+use crate::{
+    init::{Register, Variables},
+    xstd::{Operand, Routine, Xstd},
+};
+
+// blocks start with a 16 byte header holding the block size, pointers handed out are 16 byte aligned.
+// small blocks are powers of two from 32 to 4096 bytes carved out of 1 MiB mmap arenas and recycled
+// through one free list per size, bigger blocks get their own mapping and are unmapped on free.
+
+/// allocates rdi bytes, returns the pointer in rax or 0 when out of memory
+static ALLOC: Routine = Routine {
+    name: "xstd_alloc",
+    args: &[Register::rdi],
+    deps: &[],
+    rodata: &[],
+    bss: &[
+        ("", Variables::AsIs("xstd_heap_free: resq 8")),
+        ("xstd_heap_cur", Variables::U64(0)),
+        ("xstd_heap_end", Variables::U64(0)),
+    ],
+    body: "
+    cmp rdi, -4112 ; the header and rounding up to a page below must not wrap
+    ja .fail
+    lea rax, [rdi + 16]
+    cmp rax, 4096
+    ja .large
+    mov ecx, 32
+    xor edx, edx
+    .class:
+    cmp rcx, rax
+    jae .found
+    shl rcx, 1
+    inc edx
+    jmp .class
+    .found:
+    mov rax, [xstd_heap_free + rdx * 8]
+    test rax, rax
+    jz .carve
+    mov r8, [rax + 8]
+    mov [xstd_heap_free + rdx * 8], r8
+    jmp .done
+    .carve:
+    mov rax, [xstd_heap_cur]
+    lea r8, [rax + rcx]
+    cmp r8, [xstd_heap_end]
+    jbe .take
+    push rcx
+    xor edi, edi
+    mov esi, 1048576
+    mov edx, 3
+    mov r10d, 0x22
+    mov r8, -1
+    xor r9d, r9d
+    mov eax, 9
+    syscall
+    pop rcx
+    cmp rax, -4096
+    ja .fail
+    lea r8, [rax + 1048576]
+    mov [xstd_heap_end], r8
+    lea r8, [rax + rcx]
+    .take:
+    mov [xstd_heap_cur], r8
+    .done:
+    mov [rax], rcx
+    add rax, 16
+    ret
+    .large:
+    add rax, 4095
+    and rax, -4096
+    push rax
+    mov rsi, rax
+    xor edi, edi
+    mov edx, 3
+    mov r10d, 0x22
+    mov r8, -1
+    xor r9d, r9d
+    mov eax, 9
+    syscall
+    pop rcx
+    cmp rax, -4096
+    jbe .done
+    .fail:
+    xor eax, eax
+    ret
+",
+};
+
+/// frees the block at rdi, 0 is ignored
+static FREE: Routine = Routine {
+    name: "xstd_free",
+    args: &[Register::rdi],
+    deps: &[&ALLOC],
+    rodata: &[],
+    bss: &[],
+    body: "
+    test rdi, rdi
+    jz .done
+    sub rdi, 16
+    mov rsi, [rdi]
+    cmp rsi, 4096
+    ja .large
+    bsr rcx, rsi
+    sub ecx, 5
+    mov rax, [xstd_heap_free + rcx * 8]
+    mov [rdi + 8], rax
+    mov [xstd_heap_free + rcx * 8], rdi
+    .done:
+    ret
+    .large:
+    mov eax, 11
+    syscall
+    ret
+",
+};
+
+/// resizes the block at rdi to rsi bytes, returns the (possibly moved) pointer in rax.
+/// on failure rax is 0 and the old block is left alone, a 0 pointer allocates a new block
+static REALLOC: Routine = Routine {
+    name: "xstd_realloc",
+    args: &[Register::rdi, Register::rsi],
+    deps: &[&ALLOC, &FREE],
+    rodata: &[],
+    bss: &[],
+    body: "
+    test rdi, rdi
+    jnz .resize
+    mov rdi, rsi
+    jmp xstd_alloc
+    .resize:
+    mov rax, [rdi - 16]
+    sub rax, 16
+    cmp rsi, rax
+    jbe .same
+    push rbx
+    push r12
+    push r13
+    mov rbx, rdi
+    mov r12, rax
+    mov rdi, rsi
+    call xstd_alloc
+    test rax, rax
+    jz .out
+    mov r13, rax
+    mov rdi, rax
+    mov rsi, rbx
+    mov rcx, r12
+    rep movsb
+    mov rdi, rbx
+    call xstd_free
+    mov rax, r13
+    .out:
+    pop r13
+    pop r12
+    pop rbx
+    ret
+    .same:
+    mov rax, rdi
+    ret
+",
+};
+
+impl<'a> Xstd<'a> {
+    /// allocates ``size`` bytes of 16 byte aligned memory, rax holds the pointer or 0 when out of memory
+    pub fn xalloc(&mut self, size: Operand) {
+        self.load_args(&[(Register::rdi, size)]);
+        self.call_routine(&ALLOC);
+    }
+
+    /// resizes a block from ``xalloc`` keeping its contents, rax holds the new pointer or 0
+    /// when out of memory (the old block stays valid then)
+    pub fn xrealloc(&mut self, ptr: Operand, size: Operand) {
+        self.load_args(&[(Register::rdi, ptr), (Register::rsi, size)]);
+        self.call_routine(&REALLOC);
+    }
+
+    /// gives a block from ``xalloc`` back, freeing 0 does nothing
+    pub fn xfree(&mut self, ptr: Operand) {
+        self.load_args(&[(Register::rdi, ptr)]);
+        self.call_routine(&FREE);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{
+    init::{Register, Variables},
+    instructions::Instruction,
+    xstd::{Operand, Routine, Xstd},
+};
+
+/// returns argv[rdi] in rax, or 0 if rdi is not below argc
+static ARG: Routine = Routine {
+    name: "xstd_arg",
+    args: &[Register::rdi],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    xor eax, eax
+    cmp rdi, [xstd_argc]
+    jae .done
+    mov rax, [xstd_argv]
+    mov rax, [rax + rdi * 8]
+    .done:
+    ret
+",
+};
+
+/// looks up the environment variable named by the 0 terminated string at rdi,
+/// returns a pointer to its value in rax or 0 if it is not set
+static GETENV: Routine = Routine {
+    name: "xstd_getenv",
+    args: &[Register::rdi],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    mov r8, [xstd_envp]
+    .next:
+    mov rax, [r8]
+    test rax, rax
+    jz .done
+    add r8, 8
+    mov rsi, rdi
+    .name:
+    movzx ecx, byte [rsi]
+    test ecx, ecx
+    jz .name_end
+    cmp cl, [rax]
+    jne .next
+    inc rsi
+    inc rax
+    jmp .name
+    .name_end:
+    cmp byte [rax], '='
+    jne .next
+    inc rax
+    .done:
+    ret
+",
+};
+
+impl<'a> Xstd<'a> {
+    /// adds ``xstd_argc``, ``xstd_argv`` and ``xstd_envp`` and the startup code filling them from the
+    /// initial stack, which holds argc followed by the 0 terminated argv and envp pointer arrays
+    pub(crate) fn use_args(&mut self) {
+        if self.parent.get_variable("xstd_argc").is_some() {
+            return;
+        }
+        self.parent.direct_add_mut_var(Variables::U64(0), "xstd_argc");
+        self.parent.direct_add_mut_var(Variables::U64(0), "xstd_argv");
+        self.parent.direct_add_mut_var(Variables::U64(0), "xstd_envp");
+        for line in [
+            "mov rax, [rsp]",
+            "mov [xstd_argc], rax",
+            "lea rcx, [rsp + 8]",
+            "mov [xstd_argv], rcx",
+            "lea rcx, [rcx + rax * 8 + 8]",
+            "mov [xstd_envp], rcx",
+        ] {
+            self.parent.emit_startup(Instruction::AsIs(line));
+        }
+    }
+
+    /// loads the amount of command line arguments (including the program name) into ``dst``
+    pub fn xargc(&mut self, dst: Register) {
+        self.use_args();
+        let load = format!("mov {:?}, [xstd_argc]", dst);
+        self.parent.emit(Instruction::AsIs(Box::leak(load.into_boxed_str())));
+    }
+
+    /// loads a pointer to the 0 terminated argument ``index`` into ``dst``, or 0 if there are not that many.
+    /// argument 0 is the program name
+    pub fn xarg(&mut self, dst: Register, index: Operand) {
+        self.use_args();
+        self.load_args(&[(Register::rdi, index)]);
+        self.call_routine(&ARG);
+        if dst != Register::rax {
+            self.parent.emit(Instruction::Mov { dst, src: Register::rax });
+        }
+    }
+
+    /// runs ``body`` once for every argument starting at ``first``, with ``dst`` pointing to the
+    /// 0 terminated argument. the body may clobber any register but must leave the stack balanced
+    pub fn xfor_each_arg<F: FnOnce(&mut Self)>(&mut self, first: i64, dst: Register, body: F) {
+        self.use_args();
+        let id = self.parent.next_label_id();
+        let index: &'static str = Box::leak(format!("xstd_arg_index_{}", id).into_boxed_str());
+        self.parent.direct_add_mut_var(Variables::U64(0), index);
+        let init = format!("mov qword [{}], {}", index, first);
+        self.parent.emit(Instruction::AsIs(Box::leak(init.into_boxed_str())));
+        self.parent.emit(Instruction::Label(format!("args_next_{}", id)));
+        self.xarg(Register::rax, Operand::Var(index));
+        self.parent.emit(Instruction::AsIs("test rax, rax"));
+        self.parent.emit(Instruction::Je(format!("args_done_{}", id)));
+        if dst != Register::rax {
+            self.parent.emit(Instruction::Mov { dst, src: Register::rax });
+        }
+        body(self);
+        let next = format!("inc qword [{}]", index);
+        self.parent.emit(Instruction::AsIs(Box::leak(next.into_boxed_str())));
+        self.parent.emit(Instruction::Jmp(format!("args_next_{}", id)));
+        self.parent.emit(Instruction::Label(format!("args_done_{}", id)));
+    }
+
+    /// loads a pointer to the 0 terminated value of the environment variable ``name`` into ``dst``,
+    /// or 0 if it is not set
+    pub fn xgetenv(&mut self, dst: Register, name: &str) {
+        self.use_args();
+        let label = self.parent.intern_str(name);
+        self.parent.emit(Instruction::MovIntoVar { reg: Register::rdi, var_name: label });
+        self.call_routine(&GETENV);
+        if dst != Register::rax {
+            self.parent.emit(Instruction::Mov { dst, src: Register::rax });
+        }
+    }
+}
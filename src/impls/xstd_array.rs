@@ -0,0 +1,130 @@
+use crate::{
+    init::{ElemType, Register, Variables, XmmRegister},
+    instructions::Instruction,
+    xstd::{Operand, Xstd},
+};
+
+/// indexed access to ``Variables::Array``, indices count elements and are scaled by the element size
+impl<'a> Xstd<'a> {
+    fn array_elem(&self, array: &'static str) -> ElemType {
+        match self.parent.get_variable(array) {
+            Some(Variables::Array { elem, .. }) => elem,
+            _ => panic!("xstd: `{}` is not an array", array),
+        }
+    }
+
+    /// address expression of element ``index``, a ``Var`` index is loaded into ``scratch`` first
+    fn elem_addr(&mut self, array: &'static str, index: Operand, scratch: Register) -> &'static str {
+        let size = self.array_elem(array).size();
+        let addr = match index {
+            Operand::Imm(index) => format!("{} + {}", array, index * size as i64),
+            Operand::Reg(reg) => format!("{} + {:?} * {}", array, reg, size),
+            Operand::Var(_) => {
+                self.load_args(&[(scratch, index)]);
+                format!("{} + {:?} * {}", array, scratch, size)
+            }
+            Operand::Addr(var) => panic!("xstd: the address of `{}` can not be used as an index", var),
+        };
+        Box::leak(addr.into_boxed_str())
+    }
+
+    /// loads element ``index`` of ``array`` into ``dst``, sign or zero extended to 64 bits.
+    /// float elements are loaded as their bit pattern
+    pub fn xload_elem(&mut self, dst: Register, array: &'static str, index: Operand) {
+        let kind = match self.array_elem(array) {
+            ElemType::F32 => Variables::U32(0),
+            ElemType::F64 => Variables::U64(0),
+            elem => elem.scalar(),
+        };
+        let addr = self.elem_addr(array, index, dst);
+        self.load_var(dst, addr, kind);
+    }
+
+    /// stores the low bytes of ``src`` into element ``index`` of ``array``, clobbers r11 for a ``Var`` index
+    pub fn xstore_elem(&mut self, array: &'static str, index: Operand, src: Register) {
+        if src == Register::r11 && matches!(index, Operand::Var(_)) {
+            panic!("xstore_elem: the value can not be in r11, it is used to hold the index into `{}`", array);
+        }
+        let size = self.array_elem(array).size();
+        let addr = self.elem_addr(array, index, Register::r11);
+        let store = format!("mov [{}], {}", addr, src.sized(size));
+        self.parent.emit(Instruction::AsIs(Box::leak(store.into_boxed_str())));
+    }
+
+    /// loads the address of element ``index`` of ``array`` into ``dst``
+    pub fn xelem_addr(&mut self, dst: Register, array: &'static str, index: Operand) {
+        let addr = self.elem_addr(array, index, dst);
+        self.parent.emit(Instruction::LeaIntoVar { reg: dst, var_name: addr });
+    }
+
+    /// loads element ``index`` of a ``F32``/``F64`` array into ``dst`` as a double,
+    /// clobbers r11 for a ``Var`` index
+    pub fn xload_elem_xmm(&mut self, dst: XmmRegister, array: &'static str, index: Operand) {
+        let op = match self.array_elem(array) {
+            ElemType::F32 => "cvtss2sd",
+            ElemType::F64 => "movsd",
+            _ => panic!("xload_elem_xmm: `{}` is not a float array", array),
+        };
+        let addr = self.elem_addr(array, index, Register::r11);
+        let load = format!("{} {:?}, [{}]", op, dst, addr);
+        self.parent.emit(Instruction::AsIs(Box::leak(load.into_boxed_str())));
+    }
+
+    /// stores the double in ``src`` into element ``index`` of a ``F32``/``F64`` array. clobbers r11 for a
+    /// ``Var`` index and xmm15 when rounding to a ``F32``
+    pub fn xstore_elem_xmm(&mut self, array: &'static str, index: Operand, src: XmmRegister) {
+        let elem = self.array_elem(array);
+        if !elem.is_float() {
+            panic!("xstore_elem_xmm: `{}` is not a float array", array);
+        }
+        let addr = self.elem_addr(array, index, Register::r11);
+        let store = match elem {
+            ElemType::F32 => format!("cvtsd2ss xmm15, {:?}\nmovss [{}], xmm15", src, addr),
+            _ => format!("movsd [{}], {:?}", addr, src),
+        };
+        for line in Box::leak(store.into_boxed_str()).lines() {
+            self.parent.emit(Instruction::AsIs(line));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        asm_makers::linx8664::mk_asm_linx8664,
+        init::{ArrayInit, ElemType, LinuxX8664, Register, Variables},
+        xstd::{Operand, Xstd},
+    };
+
+    fn with_array<F: FnOnce(&mut Xstd)>(elem: ElemType, body: F) -> String {
+        let mut x = LinuxX8664::new();
+        x.add_mutable_variable(Variables::Array { elem, len: 8, init: ArrayInit::Zero }, "a");
+        x.add_variable(Variables::U64(3), "i");
+        body(&mut Xstd::new(&mut x));
+        mk_asm_linx8664(&x)
+    }
+
+    #[test]
+    fn indices_are_scaled_by_the_element_size() {
+        let asm = with_array(ElemType::I16, |s| s.xload_elem(Register::rax, "a", Operand::Imm(3)));
+        assert!(asm.contains("movsx rax, word [a + 6]"), "{}", asm);
+        let asm = with_array(ElemType::U32, |s| s.xload_elem(Register::rax, "a", Operand::Reg(Register::rcx)));
+        assert!(asm.contains("mov eax, dword [a + rcx * 4]"), "{}", asm);
+        let asm = with_array(ElemType::F64, |s| s.xload_elem(Register::rdx, "a", Operand::Var("i")));
+        assert!(asm.contains("mov rdx, qword [a + rdx * 8]"), "{}", asm);
+    }
+
+    #[test]
+    fn stores_write_the_element_width() {
+        let asm = with_array(ElemType::U8, |s| s.xstore_elem("a", Operand::Imm(5), Register::rax));
+        assert!(asm.contains("mov [a + 5], al"), "{}", asm);
+        let asm = with_array(ElemType::I32, |s| s.xstore_elem("a", Operand::Var("i"), Register::rbx));
+        assert!(asm.contains("mov [a + r11 * 4], ebx"), "{}", asm);
+    }
+
+    #[test]
+    #[should_panic(expected = "can not be in r11")]
+    fn store_from_r11_with_a_var_index_panics() {
+        with_array(ElemType::U64, |s| s.xstore_elem("a", Operand::Var("i"), Register::r11));
+    }
+}
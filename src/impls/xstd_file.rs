@@ -0,0 +1,117 @@
+use crate::{
+    init::Register,
+    instructions::Instruction,
+    xstd::{Operand, Xstd},
+};
+
+/// file helpers, every one of them leaves the syscall result in rax where a negative value is
+/// ``-errno``. use ``xcheck`` right after a call to jump to an error label on failure
+impl<'a> Xstd<'a> {
+    pub const O_RDONLY: i64 = 0;
+    pub const O_WRONLY: i64 = 0o1;
+    pub const O_RDWR: i64 = 0o2;
+    pub const O_CREAT: i64 = 0o100;
+    pub const O_EXCL: i64 = 0o200;
+    pub const O_TRUNC: i64 = 0o1000;
+    pub const O_APPEND: i64 = 0o2000;
+    pub const O_NONBLOCK: i64 = 0o4000;
+    pub const O_DIRECTORY: i64 = 0o200000;
+    pub const O_CLOEXEC: i64 = 0o2000000;
+    /// directory fd meaning the current working directory for ``xopenat``
+    pub const AT_FDCWD: i64 = -100;
+    pub const SEEK_SET: i64 = 0;
+    pub const SEEK_CUR: i64 = 1;
+    pub const SEEK_END: i64 = 2;
+    /// size of the ``struct stat`` filled by ``xfstat``
+    pub const STAT_SIZE: i64 = 144;
+    /// offset of ``st_mode`` (u32) in ``struct stat``
+    pub const STAT_MODE: i64 = 24;
+    /// offset of ``st_size`` (i64) in ``struct stat``
+    pub const STAT_SIZE_OFFSET: i64 = 48;
+
+    pub(crate) fn path_var(&self, path: &'static str) -> Operand {
+        match self.parent.get_variable(path) {
+            Some(kind) if kind.is_text() => Operand::Addr(path),
+            _ => panic!("xstd: path `{}` is not a Str variable or byte array", path),
+        }
+    }
+
+    pub(crate) fn syscall(&mut self, nr: i64) {
+        self.parent.emit(Instruction::MovImm { dst: Register::rax, imm: nr });
+        self.parent.emit(Instruction::SYSCALL);
+    }
+
+    /// jumps to ``label`` if the last helper failed (rax is negative)
+    pub fn xcheck(&mut self, label: &'static str) {
+        self.parent.emit(Instruction::AsIs("test rax, rax"));
+        let jump = format!("js {}", label);
+        self.parent.emit(Instruction::AsIs(Box::leak(jump.into_boxed_str())));
+    }
+
+    /// opens the file named by the ``Str`` variable ``path`` relative to the working directory,
+    /// rax holds the new fd
+    pub fn xopen(&mut self, path: &'static str, flags: i64, mode: i64) {
+        self.xopenat(Operand::Imm(Self::AT_FDCWD), path, flags, mode);
+    }
+
+    /// opens ``path`` relative to the directory fd ``dir``, rax holds the new fd
+    pub fn xopenat(&mut self, dir: Operand, path: &'static str, flags: i64, mode: i64) {
+        let path = self.path_var(path);
+        self.load_args(&[
+            (Register::rdi, dir),
+            (Register::rsi, path),
+            (Register::rdx, Operand::Imm(flags)),
+            (Register::r10, Operand::Imm(mode)),
+        ]);
+        self.syscall(257);
+    }
+
+    /// reads up to ``count`` bytes from ``fd`` into ``buf``, rax holds the amount read
+    pub fn xread(&mut self, fd: Operand, buf: Operand, count: Operand) {
+        self.load_args(&[(Register::rdi, fd), (Register::rsi, buf), (Register::rdx, count)]);
+        self.syscall(0);
+    }
+
+    /// writes up to ``count`` bytes of ``buf`` to ``fd``, rax holds the amount written.
+    /// this does not go through the stdout buffer, ``xflush`` first when writing to fd 1
+    pub fn xwrite(&mut self, fd: Operand, buf: Operand, count: Operand) {
+        self.load_args(&[(Register::rdi, fd), (Register::rsi, buf), (Register::rdx, count)]);
+        self.syscall(1);
+    }
+
+    /// moves the offset of ``fd`` (see ``SEEK_*``), rax holds the new offset
+    pub fn xlseek(&mut self, fd: Operand, offset: Operand, whence: i64) {
+        self.load_args(&[(Register::rdi, fd), (Register::rsi, offset), (Register::rdx, Operand::Imm(whence))]);
+        self.syscall(8);
+    }
+
+    pub fn xclose(&mut self, fd: Operand) {
+        self.load_args(&[(Register::rdi, fd)]);
+        self.syscall(3);
+    }
+
+    /// fills the ``STAT_SIZE`` bytes at ``statbuf`` with the ``struct stat`` of ``fd``
+    pub fn xfstat(&mut self, fd: Operand, statbuf: Operand) {
+        self.load_args(&[(Register::rdi, fd), (Register::rsi, statbuf)]);
+        self.syscall(5);
+    }
+
+    /// rax holds the size in bytes of the file opened as ``fd``, or ``-errno``
+    pub fn xfile_size(&mut self, fd: Operand) {
+        self.load_args(&[(Register::rdi, fd)]);
+        self.parent.emit(Instruction::AddImm { dst: Register::rsp, imm: -Self::STAT_SIZE });
+        self.parent.emit(Instruction::Mov { dst: Register::rsi, src: Register::rsp });
+        self.syscall(5);
+        self.parent.emit(Instruction::AsIs("test rax, rax"));
+        let size = format!("cmovns rax, [rsp + {}]", Self::STAT_SIZE_OFFSET);
+        self.parent.emit(Instruction::AsIs(Box::leak(size.into_boxed_str())));
+        self.parent.emit(Instruction::AddImm { dst: Register::rsp, imm: Self::STAT_SIZE });
+    }
+
+    /// removes the file named by the ``Str`` variable ``path``
+    pub fn xunlink(&mut self, path: &'static str) {
+        let path = self.path_var(path);
+        self.load_args(&[(Register::rdi, Operand::Imm(Self::AT_FDCWD)), (Register::rsi, path), (Register::rdx, Operand::Imm(0))]);
+        self.syscall(263);
+    }
+}
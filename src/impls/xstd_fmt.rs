@@ -0,0 +1,663 @@
+use crate::{
+    impls::{xstd_mem::STRLEN, xstd_out::WRITE},
+    init::{Register, Variables, XmmRegister},
+    instructions::Instruction,
+    xstd::{FmtArg, Routine, Xstd, BOOL_STR, DEFAULT_FLOAT_PRECISION, F64_STR},
+};
+
+/// flags passed in edx to ``xstd_fmt_int``
+const FMT_SIGNED: i64 = 1;
+const FMT_UPPER: i64 = 2;
+const FMT_ALT: i64 = 4;
+const FMT_PLUS: i64 = 8;
+const FMT_ZERO: i64 = 16;
+
+/// writes rdi copies of the byte in sil to stdout
+static FMT_FILL: Routine = Routine {
+    name: "xstd_fmt_fill",
+    args: &[Register::rdi, Register::rsi],
+    deps: &[&WRITE],
+    rodata: &[],
+    bss: &[],
+    body: "
+    test rdi, rdi
+    jz .done
+    push rbx
+    sub rsp, 64
+    mov rbx, rdi
+    mov eax, esi
+    mov rdi, rsp
+    mov ecx, 64
+    rep stosb
+    .chunk:
+    mov edx, 64
+    cmp rbx, rdx
+    cmovb rdx, rbx
+    sub rbx, rdx
+    mov rsi, rsp
+    call xstd_write
+    test rbx, rbx
+    jnz .chunk
+    add rsp, 64
+    pop rbx
+    .done:
+    ret
+",
+};
+
+/// writes rdx bytes at rsi padded with r8b up to a width of rcx,
+/// aligned right (r9 = 0), left (r9 = 1) or centered (r9 = 2)
+static FMT_WRITE: Routine = Routine {
+    name: "xstd_fmt_write",
+    args: &[Register::rsi, Register::rdx, Register::rcx, Register::r8, Register::r9],
+    deps: &[&FMT_FILL, &WRITE],
+    rodata: &[],
+    bss: &[],
+    body: "
+    push rbx
+    push r12
+    push r13
+    push r14
+    mov r12, rsi
+    mov r13, rdx
+    mov rbx, rcx
+    sub rbx, rdx
+    jg .has_pad
+    xor ebx, ebx
+    .has_pad:
+    xor r14d, r14d
+    cmp r9, 1
+    jb .emit
+    je .left
+    mov r14, rbx
+    shr rbx, 1
+    sub r14, rbx
+    jmp .emit
+    .left:
+    mov r14, rbx
+    xor ebx, ebx
+    .emit:
+    mov rdi, rbx
+    mov esi, r8d
+    call xstd_fmt_fill
+    mov rsi, r12
+    mov rdx, r13
+    call xstd_write
+    mov rdi, r14
+    mov esi, r8d
+    call xstd_fmt_fill
+    pop r14
+    pop r13
+    pop r12
+    pop rbx
+    ret
+",
+};
+
+/// formats rdi in base rsi with the FMT_* flags in rdx, then pads it like ``xstd_fmt_write``
+static FMT_INT: Routine = Routine {
+    name: "xstd_fmt_int",
+    args: &[Register::rdi, Register::rsi, Register::rdx, Register::rcx, Register::r8, Register::r9],
+    deps: &[&FMT_WRITE],
+    rodata: &[],
+    bss: &[],
+    body: "
+    push rbx
+    push r12
+    sub rsp, 136
+    mov rax, rdi
+    mov r10, rsi
+    mov r11, rdx
+    mov r12, rcx
+    xor ebx, ebx
+    test r11, 1
+    jz .plus
+    test rax, rax
+    jns .plus
+    neg rax
+    mov ebx, '-'
+    jmp .digits
+    .plus:
+    test r11, 8
+    jz .digits
+    mov ebx, '+'
+    .digits:
+    lea rsi, [rsp + 136]
+    .digit:
+    xor edx, edx
+    div r10
+    add dl, '0'
+    cmp dl, '9'
+    jbe .store
+    add dl, 39
+    test r11, 2
+    jz .store
+    sub dl, 32
+    .store:
+    dec rsi
+    mov [rsi], dl
+    test rax, rax
+    jnz .digit
+    xor ecx, ecx
+    test ebx, ebx
+    setnz cl
+    test r11, 4
+    jz .zero_pad
+    cmp r10, 10
+    je .zero_pad
+    add ecx, 2
+    .zero_pad:
+    test r11, 16
+    jz .prefix
+    lea rax, [rsp + 136]
+    sub rax, rsi
+    add rax, rcx
+    .zero_loop:
+    cmp rax, r12
+    jge .prefix
+    lea rdx, [rsp + 8]
+    cmp rsi, rdx
+    jbe .prefix
+    dec rsi
+    mov byte [rsi], '0'
+    inc rax
+    jmp .zero_loop
+    .prefix:
+    test r11, 4
+    jz .sign
+    cmp r10, 10
+    je .sign
+    mov dl, 'x'
+    cmp r10, 2
+    jne .not_bin
+    mov dl, 'b'
+    .not_bin:
+    cmp r10, 8
+    jne .letter
+    mov dl, 'o'
+    .letter:
+    dec rsi
+    mov [rsi], dl
+    dec rsi
+    mov byte [rsi], '0'
+    .sign:
+    test ebx, ebx
+    jz .out
+    dec rsi
+    mov [rsi], bl
+    .out:
+    lea rdx, [rsp + 136]
+    sub rdx, rsi
+    mov rcx, r12
+    call xstd_fmt_write
+    add rsp, 136
+    pop r12
+    pop rbx
+    ret
+",
+};
+
+/// writes rsi bytes at rdi (up to the first 0 if rsi is -1) padded like ``xstd_fmt_write``
+static FMT_STR: Routine = Routine {
+    name: "xstd_fmt_str",
+    args: &[Register::rdi, Register::rsi, Register::rcx, Register::r8, Register::r9],
+    deps: &[&FMT_WRITE, &STRLEN],
+    rodata: &[],
+    bss: &[],
+    body: "
+    mov rdx, rsi
+    mov rsi, rdi
+    cmp rdx, -1
+    jne xstd_fmt_write
+    push rcx
+    call xstd_strlen
+    pop rcx
+    mov rsi, rdi
+    mov rdx, rax
+    jmp xstd_fmt_write
+",
+};
+
+/// formats the double in xmm0 with edi digits after the point, with a leading ``+`` for values
+/// without the sign bit if rdx has FMT_PLUS, then pads it like ``xstd_fmt_write``. clobbers xmm0
+static FMT_F64: Routine = Routine {
+    name: "xstd_fmt_f64",
+    args: &[Register::rdi, Register::rdx, Register::rcx, Register::r8, Register::r9],
+    deps: &[&F64_STR, &FMT_WRITE],
+    rodata: &[],
+    bss: &[],
+    body: "
+    push rbx
+    push r12
+    push r13
+    push r14
+    sub rsp, 344
+    mov r12, rcx
+    mov r13, r8
+    mov r14, r9
+    xor ebx, ebx
+    test rdx, 8
+    jz .format
+    movq rax, xmm0
+    test rax, rax
+    js .format
+    mov byte [rsp], '+'
+    inc ebx
+    .format:
+    lea rsi, [rsp + rbx]
+    call xstd_f64_str
+    mov rsi, rsp
+    lea rdx, [rax + rbx]
+    mov rcx, r12
+    mov r8, r13
+    mov r9, r14
+    call xstd_fmt_write
+    add rsp, 344
+    pop r14
+    pop r13
+    pop r12
+    pop rbx
+    ret
+",
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+    Right,
+    Left,
+    Center,
+}
+
+/// a parsed ``{:...}`` replacement field, follows the syntax of rust's ``std::fmt``:
+/// ``[[fill]align][+][#][0][width][.precision][type]``
+#[derive(Debug, Clone, Copy)]
+struct FmtSpec {
+    fill: u8,
+    align: Option<Align>,
+    plus: bool,
+    alt: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<u8>,
+    kind: Option<char>,
+}
+
+#[derive(Debug)]
+enum FmtPiece {
+    Text(String),
+    Arg(FmtSpec),
+}
+
+fn parse_align(c: char) -> Option<Align> {
+    match c {
+        '<' => Some(Align::Left),
+        '>' => Some(Align::Right),
+        '^' => Some(Align::Center),
+        _ => None,
+    }
+}
+
+fn parse_spec(spec: &str) -> FmtSpec {
+    let mut out = FmtSpec {
+        fill: b' ',
+        align: None,
+        plus: false,
+        alt: false,
+        zero: false,
+        width: 0,
+        precision: None,
+        kind: None,
+    };
+    let chars: Vec<char> = spec.chars().collect();
+    let mut i = 0;
+    if chars.len() >= 2 && parse_align(chars[1]).is_some() {
+        if !chars[0].is_ascii() {
+            panic!("xprintf: fill character `{}` is not ascii", chars[0]);
+        }
+        out.fill = chars[0] as u8;
+        out.align = parse_align(chars[1]);
+        i = 2;
+    } else if let Some(align) = chars.first().and_then(|c| parse_align(*c)) {
+        out.align = Some(align);
+        i = 1;
+    }
+    if chars.get(i) == Some(&'+') {
+        out.plus = true;
+        i += 1;
+    }
+    if chars.get(i) == Some(&'#') {
+        out.alt = true;
+        i += 1;
+    }
+    if chars.get(i) == Some(&'0') {
+        out.zero = true;
+        i += 1;
+    }
+    while let Some(d) = chars.get(i).and_then(|c| c.to_digit(10)) {
+        out.width = out.width * 10 + d as usize;
+        i += 1;
+    }
+    if chars.get(i) == Some(&'.') {
+        i += 1;
+        let mut precision = 0u32;
+        while let Some(d) = chars.get(i).and_then(|c| c.to_digit(10)) {
+            precision = precision * 10 + d;
+            i += 1;
+        }
+        out.precision = Some(precision.min(18) as u8);
+    }
+    match &chars[i..] {
+        [] => {}
+        [kind @ ('d' | 'i' | 'u' | 'x' | 'X' | 'o' | 'b' | 'c' | 's' | 'f')] => out.kind = Some(*kind),
+        _ => panic!("xprintf: invalid format spec `{{:{}}}`", spec),
+    }
+    out
+}
+
+/// splits a format string into text and replacement fields, ``{{`` and ``}}`` are literal braces
+fn parse_format(fmt: &str) -> Vec<FmtPiece> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut field = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => field.push(c),
+                        None => panic!("xprintf: unterminated `{{` in `{}`", fmt),
+                    }
+                }
+                let spec = match field.strip_prefix(':') {
+                    Some(spec) => parse_spec(spec),
+                    None if field.is_empty() => parse_spec(""),
+                    None => panic!("xprintf: positional and named arguments are not supported (`{{{}}}`)", field),
+                };
+                if !text.is_empty() {
+                    pieces.push(FmtPiece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(FmtPiece::Arg(spec));
+            }
+            '}' => panic!("xprintf: unmatched `}}` in `{}`", fmt),
+            _ => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(FmtPiece::Text(text));
+    }
+    pieces
+}
+
+impl<'a> Xstd<'a> {
+    /// prints a format string, the format is parsed at generation time and every
+    /// ``{}`` field consumes the next argument, e.g.
+    /// ```rust
+    /// use xasm_rs::{init::{LinuxX8664, Register, Variables}, xstd::{FmtArg, Xstd}};
+    /// let mut linuxx86 = LinuxX8664::new();
+    /// linuxx86.add_variable(Variables::I64(-42), "x");
+    /// let mut xstd = Xstd::new(&mut linuxx86);
+    /// xstd.xprintf("x = {:>8} hex={:#x}\n", vec![FmtArg::Var("x"), FmtArg::Reg(Register::rax)]);
+    /// ```
+    /// supported types are ``d``/``i`` (signed), ``u``, ``x``, ``X``, ``o``, ``b``, ``c``, ``s`` and ``f``,
+    /// without a type variables are printed by their declared type and registers as signed integers.
+    /// register arguments (also xmm registers) are saved on the stack first so they can be printed in any order
+    pub fn xprintf(&mut self, fmt: &str, args: Vec<FmtArg>) {
+        let pieces = parse_format(fmt);
+        let fields = pieces.iter().filter(|p| matches!(p, FmtPiece::Arg(_))).count();
+        if fields != args.len() {
+            panic!("xprintf: `{}` has {} fields but {} arguments were given", fmt, fields, args.len());
+        }
+        let regs: Vec<Register> = args
+            .iter()
+            .filter_map(|arg| match arg {
+                FmtArg::Reg(reg) => Some(*reg),
+                _ => None,
+            })
+            .collect();
+        let xmms: Vec<XmmRegister> = args
+            .iter()
+            .filter_map(|arg| match arg {
+                FmtArg::Xmm(reg) => Some(*reg),
+                _ => None,
+            })
+            .collect();
+        for reg in &regs {
+            self.parent.emit(Instruction::Push { reg: *reg });
+        }
+        // xmm arguments go below the registers, formatting clobbers xmm0
+        if !xmms.is_empty() {
+            let sub = format!("sub rsp, {}", 8 * xmms.len());
+            self.parent.emit(Instruction::AsIs(Box::leak(sub.into_boxed_str())));
+        }
+        for (i, reg) in xmms.iter().enumerate() {
+            let spill = format!("movsd [rsp + {}], {:?}", 8 * i, reg);
+            self.parent.emit(Instruction::AsIs(Box::leak(spill.into_boxed_str())));
+        }
+        let mut args = args.into_iter();
+        let (mut reg_index, mut xmm_index) = (0, 0);
+        for piece in pieces {
+            match piece {
+                FmtPiece::Text(text) => {
+                    let label = self.add_text(&text);
+                    self.write_label(label, text.len());
+                }
+                FmtPiece::Arg(spec) => match args.next() {
+                    Some(FmtArg::Reg(_)) => {
+                        let slot = 8 * (xmms.len() + regs.len() - 1 - reg_index);
+                        reg_index += 1;
+                        self.fmt_stack_slot(slot, spec);
+                    }
+                    Some(FmtArg::Var(var)) => self.fmt_var(var, spec),
+                    Some(FmtArg::Xmm(_)) => {
+                        if !matches!(spec.kind, None | Some('f')) {
+                            panic!("xprintf: xmm registers can only be printed as floats");
+                        }
+                        let load = format!("movsd xmm0, [rsp + {}]", 8 * xmm_index);
+                        xmm_index += 1;
+                        self.parent.emit(Instruction::AsIs(Box::leak(load.into_boxed_str())));
+                        self.fmt_f64(spec);
+                    }
+                    None => unreachable!(),
+                },
+            }
+        }
+        let saved = regs.len() + xmms.len();
+        if saved > 0 {
+            self.parent.emit(Instruction::AddImm { dst: Register::rsp, imm: 8 * saved as i64 });
+        }
+    }
+
+    /// sets up the width, fill and alignment arguments shared by the fmt routines
+    fn fmt_padding(&mut self, spec: FmtSpec, default: Align) {
+        let align = match spec.align.unwrap_or(default) {
+            Align::Right => 0,
+            Align::Left => 1,
+            Align::Center => 2,
+        };
+        self.parent.emit(Instruction::MovImm { dst: Register::rcx, imm: spec.width as i64 });
+        self.parent.emit(Instruction::MovImm { dst: Register::r8, imm: spec.fill as i64 });
+        self.parent.emit(Instruction::MovImm { dst: Register::r9, imm: align });
+    }
+
+    /// formats the integer in rdi
+    fn fmt_int(&mut self, spec: FmtSpec, signed: bool) {
+        let (base, mut flags) = match spec.kind {
+            None => (10, if signed { FMT_SIGNED } else { 0 }),
+            Some('d' | 'i') => (10, FMT_SIGNED),
+            Some('u') => (10, 0),
+            Some('x') => (16, 0),
+            Some('X') => (16, FMT_UPPER),
+            Some('o') => (8, 0),
+            Some('b') => (2, 0),
+            Some(kind) => panic!("xprintf: `{}` is not an integer conversion", kind),
+        };
+        if spec.alt {
+            flags |= FMT_ALT;
+        }
+        if spec.plus {
+            flags |= FMT_PLUS;
+        }
+        if spec.zero {
+            flags |= FMT_ZERO;
+        }
+        self.parent.emit(Instruction::MovImm { dst: Register::rsi, imm: base });
+        self.parent.emit(Instruction::MovImm { dst: Register::rdx, imm: flags });
+        self.fmt_padding(spec, Align::Right);
+        self.call_routine(&FMT_INT);
+    }
+
+    /// formats xmm0 as a double, the caller loads the value
+    fn fmt_f64(&mut self, spec: FmtSpec) {
+        if spec.zero {
+            panic!("xprintf: the `0` flag is not supported for floats");
+        }
+        let precision = spec.precision.unwrap_or(DEFAULT_FLOAT_PRECISION);
+        self.parent.emit(Instruction::MovImm { dst: Register::rdi, imm: precision as i64 });
+        self.parent.emit(Instruction::MovImm { dst: Register::rdx, imm: if spec.plus { FMT_PLUS } else { 0 } });
+        self.fmt_padding(spec, Align::Right);
+        self.call_routine(&FMT_F64);
+    }
+
+    /// formats the string at rdi, rsi holds its length or -1 if it is 0 terminated
+    fn fmt_str(&mut self, spec: FmtSpec) {
+        self.fmt_padding(spec, Align::Left);
+        self.call_routine(&FMT_STR);
+    }
+
+    /// formats a register saved at ``[rsp + slot]``
+    fn fmt_stack_slot(&mut self, slot: usize, spec: FmtSpec) {
+        match spec.kind {
+            Some('c') => {
+                let lea = format!("lea rdi, [rsp + {}]", slot);
+                self.parent.emit(Instruction::AsIs(Box::leak(lea.into_boxed_str())));
+                self.parent.emit(Instruction::MovImm { dst: Register::rsi, imm: 1 });
+                self.fmt_str(spec);
+            }
+            Some('s') => {
+                let load = format!("mov rdi, [rsp + {}]", slot);
+                self.parent.emit(Instruction::AsIs(Box::leak(load.into_boxed_str())));
+                self.parent.emit(Instruction::MovImm { dst: Register::rsi, imm: -1 });
+                self.fmt_str(spec);
+            }
+            Some('f') => panic!("xprintf: general purpose registers can not be printed as floats"),
+            _ => {
+                let load = format!("mov rdi, [rsp + {}]", slot);
+                self.parent.emit(Instruction::AsIs(Box::leak(load.into_boxed_str())));
+                self.fmt_int(spec, true);
+            }
+        }
+    }
+
+    fn fmt_var(&mut self, var: &'static str, spec: FmtSpec) {
+        let kind = self
+            .parent
+            .get_variable(var)
+            .unwrap_or_else(|| panic!("xprintf: unknown variable `{}`", var));
+        match (kind, spec.kind) {
+            (_, Some('c')) => {
+                self.parent.emit(Instruction::MovIntoVar { reg: Register::rdi, var_name: var });
+                self.parent.emit(Instruction::MovImm { dst: Register::rsi, imm: 1 });
+                self.fmt_str(spec);
+            }
+            (kind, None | Some('s')) if kind.is_text() => {
+                self.parent.emit(Instruction::MovIntoVar { reg: Register::rdi, var_name: var });
+                self.parent.emit(Instruction::MovImm { dst: Register::rsi, imm: -1 });
+                self.fmt_str(spec);
+            }
+            (Variables::Bool(_), None | Some('s')) => {
+                self.load_var(Register::rdi, var, kind);
+                self.call_routine(&BOOL_STR);
+                self.parent.emit(Instruction::Mov { dst: Register::rdi, src: Register::rax });
+                self.parent.emit(Instruction::Mov { dst: Register::rsi, src: Register::rdx });
+                self.fmt_str(spec);
+            }
+            (Variables::F32(_) | Variables::F64(_), None | Some('f')) => {
+                self.load_float_var(var, kind);
+                self.fmt_f64(spec);
+            }
+            (Variables::Bool(_), _) => {
+                self.load_var(Register::rdi, var, kind);
+                self.fmt_int(spec, false);
+            }
+            (_, Some('s' | 'f'))
+            | (
+                Variables::AsIs(_)
+                | Variables::F32(_)
+                | Variables::F64(_)
+                | Variables::Bytes(_)
+                | Variables::Array { .. }
+                | Variables::Struct { .. },
+                _,
+            ) => {
+                panic!("xprintf: `{}` can not be printed with {:?}", var, spec.kind)
+            }
+            (_, None | Some('d' | 'i')) => {
+                self.load_var(Register::rdi, var, kind);
+                self.fmt_int(spec, kind.is_signed());
+            }
+            _ => {
+                // unsigned conversions print the bits of the declared width
+                self.load_var(Register::rdi, var, kind.to_unsigned());
+                self.fmt_int(spec, false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        asm_makers::linx8664::mk_asm_linx8664,
+        init::{LinuxX8664, Register, Variables, XmmRegister},
+        xstd::{FmtArg, Xstd},
+    };
+
+    fn printf(var: Variables, fmt: &str) -> String {
+        let mut x = LinuxX8664::new();
+        x.add_variable(var, "v");
+        Xstd::new(&mut x).xprintf(fmt, vec![FmtArg::Var("v")]);
+        mk_asm_linx8664(&x)
+    }
+
+    #[test]
+    fn narrow_signed_hex_is_zero_extended() {
+        let asm = printf(Variables::I8(-1), "{:x}");
+        assert!(asm.contains("movzx edi, byte [v]"), "{}", asm);
+        assert!(!asm.contains("movsx"));
+        assert!(printf(Variables::I16(-1), "{:#b}").contains("movzx edi, word [v]"));
+        assert!(printf(Variables::I32(-1), "{:u}").contains("mov edi, dword [v]"));
+    }
+
+    #[test]
+    fn signed_decimal_is_sign_extended() {
+        assert!(printf(Variables::I8(-1), "{}").contains("movsx rdi, byte [v]"));
+        assert!(printf(Variables::I32(-1), "{:d}").contains("movsxd rdi, dword [v]"));
+    }
+
+    #[test]
+    fn xmm_arguments_are_spilled_up_front() {
+        let mut x = LinuxX8664::new();
+        Xstd::new(&mut x).xprintf(
+            "{} {} {}",
+            vec![FmtArg::Xmm(XmmRegister::xmm1), FmtArg::Reg(Register::rbx), FmtArg::Xmm(XmmRegister::xmm0)],
+        );
+        let asm = mk_asm_linx8664(&x);
+        let spill = asm.find("movsd [rsp + 8], xmm0").expect("xmm0 is spilled");
+        assert!(asm.contains("sub rsp, 16"), "{}", asm);
+        assert!(asm.find("movsd [rsp + 0], xmm1").unwrap() < spill);
+        assert!(spill < asm.find("movsd xmm0, [rsp + 0]").unwrap());
+        assert!(asm.contains("movsd xmm0, [rsp + 8]"));
+        assert!(asm.contains("mov rdi, [rsp + 16]"), "{}", asm);
+        assert!(asm.contains("add rsp, 24"));
+    }
+}
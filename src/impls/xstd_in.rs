@@ -0,0 +1,291 @@
+use crate::{
+    init::{Register, Variables},
+    instructions::Instruction,
+    xstd::{Routine, Xstd},
+};
+
+/// returns the next byte of stdin in rax, or -1 at the end of input
+static GETC: Routine = Routine {
+    name: "xstd_getc",
+    args: &[],
+    deps: &[],
+    rodata: &[],
+    bss: &[
+        ("", Variables::AsIs("xstd_in_buf: resb 4096")),
+        ("xstd_in_pos", Variables::U64(0)),
+        ("xstd_in_end", Variables::U64(0)),
+    ],
+    body: "
+    mov rax, [xstd_in_pos]
+    cmp rax, [xstd_in_end]
+    jb .have
+    xor eax, eax
+    xor edi, edi
+    mov rsi, xstd_in_buf
+    mov edx, 4096
+    syscall
+    test rax, rax
+    jle .eof
+    mov [xstd_in_end], rax
+    xor eax, eax
+    .have:
+    movzx ecx, byte [xstd_in_buf + rax]
+    inc rax
+    mov [xstd_in_pos], rax
+    mov eax, ecx
+    ret
+    .eof:
+    mov rax, -1
+    ret
+",
+};
+
+/// reads a line of stdin into rdi (at most rsi bytes, the rest of the line is dropped),
+/// strips the newline and adds a trailing 0. returns the length in rax or -1 at the end of input
+static READ_LINE: Routine = Routine {
+    name: "xstd_read_line",
+    args: &[Register::rdi, Register::rsi],
+    deps: &[&GETC],
+    rodata: &[],
+    bss: &[],
+    body: "
+    push rbx
+    push r12
+    push r13
+    mov r12, rdi
+    mov r13, rsi
+    xor ebx, ebx
+    .next:
+    call xstd_getc
+    test rax, rax
+    js .eof
+    cmp al, 10
+    je .done
+    cmp rbx, r13
+    jae .next
+    mov [r12 + rbx], al
+    inc rbx
+    jmp .next
+    .eof:
+    test rbx, rbx
+    jnz .done
+    mov byte [r12], 0
+    mov rax, -1
+    jmp .out
+    .done:
+    mov byte [r12 + rbx], 0
+    mov rax, rbx
+    .out:
+    pop r13
+    pop r12
+    pop rbx
+    ret
+",
+};
+
+/// reads stdin into rdi until the end of input or until rsi bytes were read,
+/// adds a trailing 0 and returns the amount of bytes read in rax
+static READ_ALL: Routine = Routine {
+    name: "xstd_read_all",
+    args: &[Register::rdi, Register::rsi],
+    deps: &[&GETC],
+    rodata: &[],
+    bss: &[],
+    body: "
+    push rbx
+    push r12
+    push r13
+    mov r12, rdi
+    mov r13, rsi
+    mov rsi, [xstd_in_pos]
+    mov rcx, [xstd_in_end]
+    sub rcx, rsi
+    cmp rcx, r13
+    cmova rcx, r13
+    mov rbx, rcx
+    add [xstd_in_pos], rcx
+    lea rsi, [xstd_in_buf + rsi]
+    mov rdi, r12
+    rep movsb
+    .loop:
+    mov rdx, r13
+    sub rdx, rbx
+    jz .done
+    xor eax, eax
+    xor edi, edi
+    lea rsi, [r12 + rbx]
+    syscall
+    test rax, rax
+    jle .done
+    add rbx, rax
+    jmp .loop
+    .done:
+    mov byte [r12 + rbx], 0
+    mov rax, rbx
+    pop r13
+    pop r12
+    pop rbx
+    ret
+",
+};
+
+/// parses the 0 terminated decimal number at rdi, signed if rsi is non zero.
+/// surrounding whitespace is allowed. returns the value in rax and rdx = 0,
+/// or rax = 0 and rdx = 1 on empty input, invalid characters or overflow
+static PARSE_INT: Routine = Routine {
+    name: "xstd_parse_int",
+    args: &[Register::rdi, Register::rsi],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    xor eax, eax
+    xor r8d, r8d
+    xor r9d, r9d
+    mov r10d, 10
+    .skip:
+    movzx ecx, byte [rdi]
+    cmp ecx, ' '
+    je .ws
+    cmp ecx, 9
+    jb .sign
+    cmp ecx, 13
+    ja .sign
+    .ws:
+    inc rdi
+    jmp .skip
+    .sign:
+    cmp ecx, '+'
+    je .sign_done
+    cmp ecx, '-'
+    jne .digits
+    test rsi, rsi
+    jz .bad
+    mov r8d, 1
+    .sign_done:
+    inc rdi
+    .digits:
+    movzx ecx, byte [rdi]
+    sub ecx, '0'
+    cmp ecx, 9
+    ja .end
+    mul r10
+    jc .bad
+    add rax, rcx
+    jc .bad
+    inc r9
+    inc rdi
+    jmp .digits
+    .end:
+    test r9, r9
+    jz .bad
+    .trail:
+    movzx ecx, byte [rdi]
+    test ecx, ecx
+    jz .check
+    cmp ecx, ' '
+    je .trail_ws
+    cmp ecx, 9
+    jb .bad
+    cmp ecx, 13
+    ja .bad
+    .trail_ws:
+    inc rdi
+    jmp .trail
+    .check:
+    test rsi, rsi
+    jz .ok
+    test r8, r8
+    jnz .negative
+    test rax, rax
+    js .bad
+    jmp .ok
+    .negative:
+    neg rax
+    test rax, rax
+    jg .bad
+    .ok:
+    xor edx, edx
+    ret
+    .bad:
+    xor eax, eax
+    mov edx, 1
+    ret
+",
+};
+
+impl<'a> Xstd<'a> {
+    /// bytes that fit in a ``Str`` buffer while keeping room for the trailing 0
+    fn buffer_capacity(&self, buf: &'static str) -> usize {
+        match self.parent.get_variable(buf) {
+            Some(Variables::Str(val)) => val.len(),
+            Some(kind) if kind.is_text() && kind.size() > 0 => kind.size() - 1,
+            _ => panic!("xstd: `{}` is not a Str or byte array buffer", buf),
+        }
+    }
+
+    /// reads a line of stdin into ``buf``, a ``U8`` array in ``.bss`` (or a ``Str`` variable whose length is the
+    /// capacity) keeping one byte for the trailing 0. the newline is stripped and the rest of a too long line is dropped.
+    /// rax holds the length of the line, or -1 at the end of input. pending stdout output (a prompt) is flushed first
+    pub fn xread_line(&mut self, buf: &'static str) {
+        let capacity = self.buffer_capacity(buf);
+        self.flush_if_buffered();
+        self.parent.emit(Instruction::MovIntoVar { reg: Register::rdi, var_name: buf });
+        self.parent.emit(Instruction::MovImm { dst: Register::rsi, imm: capacity as i64 });
+        self.call_routine(&READ_LINE);
+    }
+
+    /// reads stdin into ``buf`` until the end of input or until the buffer is full, after flushing
+    /// pending stdout output. rax holds the amount of bytes read
+    pub fn xread_all(&mut self, buf: &'static str) {
+        let capacity = self.buffer_capacity(buf);
+        self.flush_if_buffered();
+        self.parent.emit(Instruction::MovIntoVar { reg: Register::rdi, var_name: buf });
+        self.parent.emit(Instruction::MovImm { dst: Register::rsi, imm: capacity as i64 });
+        self.call_routine(&READ_ALL);
+    }
+
+    fn parse_int(&mut self, buf: &'static str, signed: bool) {
+        self.parent.emit(Instruction::MovIntoVar { reg: Register::rdi, var_name: buf });
+        self.parent.emit(Instruction::MovImm { dst: Register::rsi, imm: signed as i64 });
+        self.call_routine(&PARSE_INT);
+    }
+
+    /// parses the decimal number in ``buf`` into ``dst`` and jumps to ``on_error`` if it is not a
+    /// valid number or does not fit in 64 bits. rdx is 1 on errors and 0 otherwise
+    pub fn xparse_int(&mut self, buf: &'static str, dst: Register, signed: bool, on_error: &'static str) {
+        self.parse_int(buf, signed);
+        self.parent.emit(Instruction::AsIs("test rdx, rdx"));
+        self.parent.emit(Instruction::Jne(on_error.to_string()));
+        if dst != Register::rax {
+            self.parent.emit(Instruction::Mov { dst, src: Register::rax });
+        }
+    }
+
+    /// parses the decimal number in ``buf`` into the integer variable ``var``, using its signedness and
+    /// width. jumps to ``on_error`` without storing anything if the input is invalid or out of range
+    pub fn xparse_int_into(&mut self, buf: &'static str, var: &'static str, on_error: &'static str) {
+        let kind = match self.parent.get_variable(var) {
+            Some(kind) if kind.is_integer() => kind,
+            _ => panic!("xparse_int_into: `{}` is not an integer variable", var),
+        };
+        self.parse_int(buf, kind.is_signed());
+        self.parent.emit(Instruction::AsIs("test rdx, rdx"));
+        self.parent.emit(Instruction::Jne(on_error.to_string()));
+        let check = match kind {
+            Variables::I8(_) => Some("movsx rcx, al"),
+            Variables::I16(_) => Some("movsx rcx, ax"),
+            Variables::I32(_) => Some("movsxd rcx, eax"),
+            Variables::U8(_) => Some("movzx ecx, al"),
+            Variables::U16(_) => Some("movzx ecx, ax"),
+            Variables::U32(_) => Some("mov ecx, eax"),
+            _ => None,
+        };
+        if let Some(check) = check {
+            self.parent.emit(Instruction::AsIs(check));
+            self.parent.emit(Instruction::Cmp { op1: Register::rcx, op2: Register::rax });
+            self.parent.emit(Instruction::Jne(on_error.to_string()));
+        }
+        self.store_var(var, Register::rax);
+    }
+}
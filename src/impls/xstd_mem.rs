@@ -0,0 +1,286 @@
+use crate::{
+    init::Register,
+    xstd::{Operand, Routine, Xstd},
+};
+
+// all routines follow the System V calling convention: arguments in rdi, rsi, rdx and the result in rax.
+// besides the argument registers they clobber at most rcx, r8 and xmm0-xmm3
+
+/// copies rdx bytes from rsi to rdi (regions must not overlap), returns rdi
+pub(crate) static MEMCPY: Routine = Routine {
+    name: "xstd_memcpy",
+    args: &[Register::rdi, Register::rsi, Register::rdx],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    mov rax, rdi
+    cmp rdx, 64
+    jb .tail
+    .sse:
+    movdqu xmm0, [rsi]
+    movdqu xmm1, [rsi + 16]
+    movdqu xmm2, [rsi + 32]
+    movdqu xmm3, [rsi + 48]
+    movdqu [rdi], xmm0
+    movdqu [rdi + 16], xmm1
+    movdqu [rdi + 32], xmm2
+    movdqu [rdi + 48], xmm3
+    add rsi, 64
+    add rdi, 64
+    sub rdx, 64
+    cmp rdx, 64
+    jae .sse
+    .tail:
+    mov rcx, rdx
+    rep movsb
+    ret
+",
+};
+
+/// copies rdx bytes from rsi to rdi, the regions may overlap. returns rdi
+static MEMMOVE: Routine = Routine {
+    name: "xstd_memmove",
+    args: &[Register::rdi, Register::rsi, Register::rdx],
+    deps: &[&MEMCPY],
+    rodata: &[],
+    bss: &[],
+    body: "
+    cmp rdi, rsi
+    jbe xstd_memcpy
+    lea rax, [rsi + rdx]
+    cmp rdi, rax
+    jae xstd_memcpy
+    mov rax, rdi
+    add rsi, rdx
+    add rdi, rdx
+    cmp rdx, 64
+    jb .tail
+    .sse:
+    sub rsi, 64
+    sub rdi, 64
+    movdqu xmm0, [rsi]
+    movdqu xmm1, [rsi + 16]
+    movdqu xmm2, [rsi + 32]
+    movdqu xmm3, [rsi + 48]
+    movdqu [rdi], xmm0
+    movdqu [rdi + 16], xmm1
+    movdqu [rdi + 32], xmm2
+    movdqu [rdi + 48], xmm3
+    sub rdx, 64
+    cmp rdx, 64
+    jae .sse
+    .tail:
+    mov rcx, rdx
+    dec rsi
+    dec rdi
+    std
+    rep movsb
+    cld
+    ret
+",
+};
+
+/// fills rdx bytes at rdi with the byte in sil, returns rdi
+static MEMSET: Routine = Routine {
+    name: "xstd_memset",
+    args: &[Register::rdi, Register::rsi, Register::rdx],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    mov r8, rdi
+    movzx eax, sil
+    cmp rdx, 16
+    jb .tail
+    movd xmm0, eax
+    punpcklbw xmm0, xmm0
+    pshuflw xmm0, xmm0, 0
+    punpcklqdq xmm0, xmm0
+    .sse:
+    movdqu [rdi], xmm0
+    add rdi, 16
+    sub rdx, 16
+    cmp rdx, 16
+    jae .sse
+    .tail:
+    mov rcx, rdx
+    rep stosb
+    mov rax, r8
+    ret
+",
+};
+
+/// compares rdx bytes at rdi and rsi, returns the difference of the first differing bytes in eax
+static MEMCMP: Routine = Routine {
+    name: "xstd_memcmp",
+    args: &[Register::rdi, Register::rsi, Register::rdx],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    xor ecx, ecx
+    .sse:
+    mov rax, rdx
+    sub rax, rcx
+    cmp rax, 16
+    jb .bytes
+    movdqu xmm0, [rdi + rcx]
+    movdqu xmm1, [rsi + rcx]
+    pcmpeqb xmm0, xmm1
+    pmovmskb eax, xmm0
+    cmp eax, 0xffff
+    jne .found
+    add rcx, 16
+    jmp .sse
+    .found:
+    not eax
+    bsf eax, eax
+    add rcx, rax
+    jmp .diff
+    .bytes:
+    cmp rcx, rdx
+    jae .equal
+    movzx eax, byte [rdi + rcx]
+    cmp al, [rsi + rcx]
+    jne .diff
+    inc rcx
+    jmp .bytes
+    .diff:
+    movzx eax, byte [rdi + rcx]
+    movzx r8d, byte [rsi + rcx]
+    sub eax, r8d
+    ret
+    .equal:
+    xor eax, eax
+    ret
+",
+};
+
+/// returns the length of the 0 terminated string at rdi
+pub(crate) static STRLEN: Routine = Routine {
+    name: "xstd_strlen",
+    args: &[Register::rdi],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    mov rax, rdi
+    .align:
+    test al, 15
+    jz .sse
+    cmp byte [rax], 0
+    je .done
+    inc rax
+    jmp .align
+    .sse:
+    pxor xmm0, xmm0
+    .loop:
+    movdqa xmm1, [rax]
+    pcmpeqb xmm1, xmm0
+    pmovmskb ecx, xmm1
+    test ecx, ecx
+    jnz .found
+    add rax, 16
+    jmp .loop
+    .found:
+    bsf ecx, ecx
+    add rax, rcx
+    .done:
+    sub rax, rdi
+    ret
+",
+};
+
+/// compares the 0 terminated strings at rdi and rsi, returns the difference of the first differing bytes
+static STRCMP: Routine = Routine {
+    name: "xstd_strcmp",
+    args: &[Register::rdi, Register::rsi],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    .loop:
+    movzx eax, byte [rdi]
+    movzx ecx, byte [rsi]
+    cmp eax, ecx
+    jne .diff
+    test eax, eax
+    jz .diff
+    inc rdi
+    inc rsi
+    jmp .loop
+    .diff:
+    sub eax, ecx
+    ret
+",
+};
+
+/// returns a pointer to the first sil byte in the 0 terminated string at rdi, or 0 if there is none
+static STRCHR: Routine = Routine {
+    name: "xstd_strchr",
+    args: &[Register::rdi, Register::rsi],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    .loop:
+    movzx eax, byte [rdi]
+    cmp al, sil
+    je .found
+    test eax, eax
+    jz .none
+    inc rdi
+    jmp .loop
+    .found:
+    mov rax, rdi
+    ret
+    .none:
+    xor eax, eax
+    ret
+",
+};
+
+impl<'a> Xstd<'a> {
+    /// copies ``n`` bytes from ``src`` to ``dst``, the regions must not overlap
+    pub fn xmemcpy(&mut self, dst: Operand, src: Operand, n: Operand) {
+        self.load_args(&[(Register::rdi, dst), (Register::rsi, src), (Register::rdx, n)]);
+        self.call_routine(&MEMCPY);
+    }
+
+    /// copies ``n`` bytes from ``src`` to ``dst``, the regions may overlap
+    pub fn xmemmove(&mut self, dst: Operand, src: Operand, n: Operand) {
+        self.load_args(&[(Register::rdi, dst), (Register::rsi, src), (Register::rdx, n)]);
+        self.call_routine(&MEMMOVE);
+    }
+
+    /// fills ``n`` bytes at ``dst`` with the low byte of ``byte``
+    pub fn xmemset(&mut self, dst: Operand, byte: Operand, n: Operand) {
+        self.load_args(&[(Register::rdi, dst), (Register::rsi, byte), (Register::rdx, n)]);
+        self.call_routine(&MEMSET);
+    }
+
+    /// compares ``n`` bytes, eax is negative, 0 or positive like C's memcmp
+    pub fn xmemcmp(&mut self, a: Operand, b: Operand, n: Operand) {
+        self.load_args(&[(Register::rdi, a), (Register::rsi, b), (Register::rdx, n)]);
+        self.call_routine(&MEMCMP);
+    }
+
+    /// rax holds the length of the 0 terminated string at ``s``
+    pub fn xstrlen(&mut self, s: Operand) {
+        self.load_args(&[(Register::rdi, s)]);
+        self.call_routine(&STRLEN);
+    }
+
+    /// compares two 0 terminated strings, eax is negative, 0 or positive like C's strcmp
+    pub fn xstrcmp(&mut self, a: Operand, b: Operand) {
+        self.load_args(&[(Register::rdi, a), (Register::rsi, b)]);
+        self.call_routine(&STRCMP);
+    }
+
+    /// rax points to the first ``c`` in the 0 terminated string at ``s``, or is 0 if there is none
+    pub fn xstrchr(&mut self, s: Operand, c: Operand) {
+        self.load_args(&[(Register::rdi, s), (Register::rsi, c)]);
+        self.call_routine(&STRCHR);
+    }
+}
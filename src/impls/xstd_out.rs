@@ -0,0 +1,91 @@
+use crate::{
+    init::{Register, Variables},
+    xstd::{Routine, Xstd},
+};
+
+// everything printed by xstd goes through ``xstd_write`` (rsi = data, rdx = length)
+// into a 4096 byte stdout buffer
+
+/// writes all rdx bytes at rsi to stdout, retrying on partial writes and giving up on errors
+pub(crate) static WRITE_ALL: Routine = Routine {
+    name: "xstd_write_all",
+    args: &[Register::rsi, Register::rdx],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    .loop:
+    test rdx, rdx
+    jle .done
+    mov eax, 1
+    mov edi, 1
+    syscall
+    test rax, rax
+    jle .done
+    add rsi, rax
+    sub rdx, rax
+    jmp .loop
+    .done:
+    ret
+",
+};
+
+/// writes out and empties the stdout buffer
+pub(crate) static FLUSH: Routine = Routine {
+    name: "xstd_flush",
+    args: &[],
+    deps: &[&WRITE_ALL],
+    rodata: &[],
+    bss: &[("", Variables::AsIs("xstd_out_buf: resb 4096")), ("xstd_out_len", Variables::U64(0))],
+    body: "
+    mov rsi, xstd_out_buf
+    mov rdx, [xstd_out_len]
+    mov qword [xstd_out_len], 0
+    jmp xstd_write_all
+",
+};
+
+/// appends rdx bytes at rsi to the stdout buffer, flushing it first when they do not fit.
+/// writes that are larger than the buffer go straight to stdout
+pub(crate) static WRITE: Routine = Routine {
+    name: "xstd_write",
+    args: &[Register::rsi, Register::rdx],
+    deps: &[&FLUSH, &WRITE_ALL],
+    rodata: &[],
+    bss: &[],
+    body: "
+    mov rax, [xstd_out_len]
+    lea rcx, [rax + rdx]
+    cmp rcx, 4096
+    jbe .copy
+    push rsi
+    push rdx
+    call xstd_flush
+    pop rdx
+    pop rsi
+    cmp rdx, 4096
+    jae xstd_write_all
+    xor eax, eax
+    .copy:
+    lea rdi, [xstd_out_buf + rax]
+    add rax, rdx
+    mov rcx, rdx
+    rep movsb
+    mov [xstd_out_len], rax ; only publish the bytes once they are copied
+    ret
+",
+};
+
+impl<'a> Xstd<'a> {
+    /// writes whatever is left in the stdout buffer, ``xexit`` does this on its own
+    pub fn xflush(&mut self) {
+        self.call_routine(&FLUSH);
+    }
+
+    /// flushes the stdout buffer if anything emitted so far prints through it
+    pub(crate) fn flush_if_buffered(&mut self) {
+        if self.parent.has_func(FLUSH.name) {
+            self.xflush();
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::panic::Location;
+use crate::{
+    impls::xstd_out::FLUSH,
+    init::Register,
+    instructions::{Condition, Instruction},
+    xstd::{Routine, Xstd},
+};
+
+/// flushes stdout, writes the rdx bytes at rsi to stderr and exits with ``Xstd::PANIC_EXIT_CODE``
+static PANIC: Routine = Routine {
+    name: "xstd_panic",
+    args: &[Register::rsi, Register::rdx],
+    deps: &[&FLUSH],
+    rodata: &[],
+    bss: &[],
+    body: "
+    push rsi
+    push rdx
+    call xstd_flush
+    pop rdx
+    pop rsi
+    .loop:
+    test rdx, rdx
+    jle .exit
+    mov eax, 1
+    mov edi, 2
+    syscall
+    test rax, rax
+    jle .exit
+    add rsi, rax
+    sub rdx, rax
+    jmp .loop
+    .exit:
+    mov edi, 101
+    mov eax, 231
+    syscall
+",
+};
+
+impl<'a> Xstd<'a> {
+    /// exit code of a program stopped by ``xpanic``, ``xassert`` or ``xbounds_check``
+    pub const PANIC_EXIT_CODE: i64 = 101;
+
+    /// prints ``message`` with the location of the rust caller and exits
+    fn panic_at(&mut self, message: &str, location: &Location) {
+        let text = format!(
+            "panicked at {}:{}:{}:\n{}\n",
+            location.file(),
+            location.line(),
+            location.column(),
+            message
+        );
+        let label = self.add_text(&text);
+        self.parent.emit(Instruction::MovIntoVar { reg: Register::rsi, var_name: label });
+        self.parent.emit(Instruction::MovImm { dst: Register::rdx, imm: text.len() as i64 });
+        self.call_routine(&PANIC);
+    }
+
+    /// prints ``message`` and the location of this call to stderr and exits with ``PANIC_EXIT_CODE``,
+    /// after flushing stdout
+    #[track_caller]
+    pub fn xpanic(&mut self, message: &str) {
+        self.panic_at(message, Location::caller());
+    }
+
+    /// panics with ``assertion failed: message`` if ``cond`` is 0
+    #[track_caller]
+    pub fn xassert(&mut self, cond: Register, message: &str) {
+        let location = Location::caller();
+        let ok = format!("assert_ok_{}", self.parent.next_label_id());
+        self.parent.emit(Instruction::AsIs(Box::leak(format!("test {:?}, {:?}", cond, cond).into_boxed_str())));
+        self.parent.emit(Instruction::Jne(ok.clone()));
+        self.panic_at(&format!("assertion failed: {}", message), location);
+        self.parent.emit(Instruction::Label(ok));
+    }
+
+    /// panics unless ``index`` (unsigned) is below the amount of ``elem_size`` byte elements that fit
+    /// in the variable ``buf``. clobbers r11 when that amount does not fit in 32 bits
+    #[track_caller]
+    pub fn xbounds_check(&mut self, index: Register, buf: &'static str, elem_size: usize) {
+        let location = Location::caller();
+        if elem_size == 0 {
+            panic!("xbounds_check: the element size of `{}` can not be 0", buf);
+        }
+        let size = match self.parent.get_variable(buf) {
+            Some(kind) if kind.size() > 0 => kind.size(),
+            _ => panic!("xbounds_check: the size of `{}` is unknown", buf),
+        };
+        let len = size / elem_size;
+        if index == Register::r11 && i32::try_from(len).is_err() {
+            panic!("xbounds_check: the index can not be in r11, it is used to hold the length of `{}`", buf);
+        }
+        let ok = format!("bounds_ok_{}", self.parent.next_label_id());
+        self.cmp_imm(index, len as i64);
+        self.parent.emit(Instruction::Jcc { cond: Condition::Below, label: ok.clone() });
+        self.panic_at(&format!("index out of bounds: `{}` has {} elements", buf, len), location);
+        self.parent.emit(Instruction::Label(ok));
+    }
+
+    /// kills the process with ``SIGABRT`` right away, without flushing stdout
+    pub fn xabort(&mut self) {
+        self.xraise(Self::SIGABRT);
+        self.parent.emit(Instruction::MovImm { dst: Register::rdi, imm: 134 });
+        self.syscall(231);
+    }
+}
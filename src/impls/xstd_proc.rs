@@ -0,0 +1,179 @@
+use crate::{
+    init::{ArrayInit, ElemType, Register, Variables},
+    instructions::Instruction,
+    xstd::{Operand, Routine, Xstd},
+};
+
+/// waits for the child rdi (-1 for any child). returns the pid or ``-errno`` in rax and the exit
+/// code of the child in rdx, 128 + the signal number if it was killed by a signal
+static WAIT: Routine = Routine {
+    name: "xstd_wait",
+    args: &[Register::rdi],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    push 0
+    mov rsi, rsp
+    xor edx, edx
+    xor r10d, r10d
+    mov eax, 61
+    syscall
+    pop rcx
+    mov edx, ecx
+    and edx, 0x7f
+    jz .exited
+    add edx, 128
+    ret
+    .exited:
+    movzx edx, ch
+    ret
+",
+};
+
+/// process helpers, like the file helpers they leave the syscall result in rax (negative values
+/// are ``-errno``) so ``xcheck`` can be used right after them
+impl<'a> Xstd<'a> {
+    /// forks the process, rax is 0 in the child and the pid of the child in the parent.
+    /// ``xflush`` first if anything was printed, or the child inherits the buffered output
+    pub fn xfork(&mut self) {
+        self.syscall(57);
+    }
+
+    /// like ``xfork`` but the child borrows the memory (and stack) of the parent, which is suspended
+    /// until the child calls ``xexecve`` or exits. the child must do nothing else
+    pub fn xvfork(&mut self) {
+        self.syscall(58);
+    }
+
+    /// adds a 0 terminated pointer array to the ``Str`` variables ``items`` and returns its label
+    fn str_array(&mut self, items: &[&'static str]) -> &'static str {
+        for item in items {
+            if !matches!(self.parent.get_variable(item), Some(Variables::Str(_))) {
+                panic!("xexecve: `{}` is not a Str variable", item);
+            }
+        }
+        let label: &'static str = Box::leak(format!("xstd_array_{}", self.parent.next_label_id()).into_boxed_str());
+        let targets: &'static [&'static str] = Box::leak(items.to_vec().into_boxed_slice());
+        let array = Variables::Array { elem: ElemType::U64, len: items.len() + 1, init: ArrayInit::Ptrs(targets) };
+        self.parent.add_readonly_variable(array, label);
+        label
+    }
+
+    /// replaces the process with the program at the ``Str`` variable ``path``. ``argv`` and ``envp`` name
+    /// ``Str`` variables (``envp`` entries look like ``NAME=value``), ``None`` passes on the current
+    /// environment. only returns on failure, with ``-errno`` in rax
+    pub fn xexecve(&mut self, path: &'static str, argv: &[&'static str], envp: Option<&[&'static str]>) {
+        let path = self.path_var(path);
+        let argv = self.str_array(argv);
+        let envp = match envp {
+            Some(envp) => Operand::Addr(self.str_array(envp)),
+            None => {
+                self.use_args();
+                Operand::Var("xstd_envp")
+            }
+        };
+        self.load_args(&[(Register::rdi, path), (Register::rsi, Operand::Addr(argv)), (Register::rdx, envp)]);
+        self.syscall(59);
+    }
+
+    /// waits for the child ``pid`` (-1 for any child), rax holds its pid and rdx its exit code,
+    /// which is 128 + the signal number if the child was killed by a signal
+    pub fn xwait4(&mut self, pid: Operand) {
+        self.load_args(&[(Register::rdi, pid)]);
+        self.call_routine(&WAIT);
+    }
+
+    /// creates a pipe and stores its ends in the integer variables ``read`` and ``write``,
+    /// ``flags`` takes ``O_CLOEXEC`` and ``O_NONBLOCK``
+    pub fn xpipe2(&mut self, read: &'static str, write: &'static str, flags: i64) {
+        self.parent.emit(Instruction::AsIs("push 0"));
+        self.parent.emit(Instruction::Mov { dst: Register::rdi, src: Register::rsp });
+        self.parent.emit(Instruction::MovImm { dst: Register::rsi, imm: flags });
+        self.syscall(293);
+        self.parent.emit(Instruction::AsIs("movsxd rcx, dword [rsp]"));
+        self.store_var(read, Register::rcx);
+        self.parent.emit(Instruction::AsIs("movsxd rcx, dword [rsp + 4]"));
+        self.store_var(write, Register::rcx);
+        self.parent.emit(Instruction::AddImm { dst: Register::rsp, imm: 8 });
+    }
+
+    /// makes ``new`` a copy of the fd ``old``, closing ``new`` first if it was open
+    pub fn xdup2(&mut self, old: Operand, new: Operand) {
+        self.load_args(&[(Register::rdi, old), (Register::rsi, new)]);
+        self.syscall(33);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process::Command};
+    use crate::{
+        asm_makers::linx8664::mk_asm_linx8664,
+        flow::{Cond, ControlFlow},
+        init::{LinuxX8664, Register, Variables},
+        instructions::Instruction,
+        xstd::{Operand, Xstd},
+    };
+
+    /// forks, runs ``path`` with the arguments ``args`` in the child and exits with the exit code of the child
+    fn run_program(path: &'static str, args: &[&'static str]) -> String {
+        let mut x = LinuxX8664::new();
+        x.add_variable(Variables::Str(path), "path");
+        let mut argv = vec!["path"];
+        for (i, arg) in args.iter().enumerate() {
+            let name: &'static str = Box::leak(format!("arg_{}", i).into_boxed_str());
+            x.add_variable(Variables::Str(arg), name);
+            argv.push(name);
+        }
+        Xstd::new(&mut x).xfork();
+        x.if_(Cond::zero(Register::rax), |b| {
+            Xstd::new(b).xexecve("path", &argv, None);
+            Xstd::new(b).xexit(127);
+        });
+        Xstd::new(&mut x).xwait4(Operand::Reg(Register::rax));
+        x.emit(Instruction::Mov { dst: Register::rdi, src: Register::rdx });
+        x.emit(Instruction::MovImm { dst: Register::rax, imm: 60 });
+        x.emit(Instruction::SYSCALL);
+        mk_asm_linx8664(&x)
+    }
+
+    /// assembles ``asm`` with nasm and ld, runs it and returns its exit code and stdout
+    fn assemble_and_run(asm: &str, name: &str) -> (Option<i32>, String) {
+        let dir = std::env::temp_dir().join(format!("xasm_proc_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (src, obj, bin) = (dir.join("prog.asm"), dir.join("prog.o"), dir.join("prog"));
+        fs::write(&src, asm).unwrap();
+        let assembled = Command::new("nasm").arg("-felf64").arg(&src).arg("-o").arg(&obj).status();
+        let assembled = assembled.expect("nasm is not installed");
+        assert!(assembled.success(), "the generated program does not assemble:\n{}", asm);
+        let linked = Command::new("ld").arg(&obj).arg("-o").arg(&bin).status().expect("ld is not installed");
+        assert!(linked.success(), "the generated program does not link");
+        let output = Command::new(&bin).output().unwrap();
+        fs::remove_dir_all(&dir).ok();
+        (output.status.code(), String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    #[test]
+    fn execve_and_wait4() {
+        let asm = run_program("/bin/true", &[]);
+        let fork = asm.find("mov rax, 57\n").expect("no fork syscall");
+        let execve = asm.find("mov rax, 59\n").expect("no execve syscall");
+        let wait = asm.find("call xstd_wait\n").expect("no wait4 call");
+        assert!(fork < execve && execve < wait);
+        assert!(asm.contains("path: db \"/bin/true\", 0"));
+        assert!(asm.contains("mov rdx, qword [xstd_envp]"));
+        assert!(asm.contains("xstd_wait:"));
+        let asm = run_program("/bin/echo", &["hello"]);
+        assert!(asm.contains("arg_0: db \"hello\", 0"));
+    }
+
+    #[test]
+    #[ignore = "needs nasm and ld, run with --ignored"]
+    fn execve_and_wait4_run() {
+        assert_eq!(assemble_and_run(&run_program("/bin/true", &[]), "true"), (Some(0), String::new()));
+        let (code, stdout) = assemble_and_run(&run_program("/bin/echo", &["hello", "world"]), "echo");
+        assert_eq!(code, Some(0));
+        assert_eq!(stdout, "hello world\n");
+    }
+}
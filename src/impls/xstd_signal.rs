@@ -0,0 +1,142 @@
+use crate::{
+    impls::xstd_out::{FLUSH, WRITE_ALL},
+    init::{Register, Variables},
+    instructions::Instruction,
+    xstd::{Routine, Xstd},
+};
+
+/// ``sa_restorer`` of every handler installed by xstd, the kernel returns here from a handler and
+/// ``rt_sigreturn`` restores the interrupted registers
+static SIGRETURN: Routine = Routine {
+    name: "xstd_sigreturn",
+    args: &[],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    mov eax, 15
+    syscall
+",
+};
+
+/// signal handler that flushes stdout and exits with 128 + the signal number in rdi.
+/// it can interrupt ``xstd_write`` or ``xstd_flush`` halfway, so instead of calling ``xstd_flush`` it only
+/// writes the bytes ``xstd_out_len`` already publishes and never touches the buffer or the length.
+/// a flush it interrupts may lose the bytes that were not written yet, nothing is written twice
+static SIG_EXIT: Routine = Routine {
+    name: "xstd_sig_exit",
+    args: &[Register::rdi],
+    deps: &[&FLUSH, &WRITE_ALL],
+    rodata: &[],
+    bss: &[],
+    body: "
+    push rdi
+    mov rsi, xstd_out_buf
+    mov rdx, [xstd_out_len]
+    call xstd_write_all
+    pop rdi
+    add edi, 128
+    mov eax, 231
+    syscall
+",
+};
+
+/// signal helpers, they leave the syscall result in rax like the file helpers
+impl<'a> Xstd<'a> {
+    pub const SIGHUP: i64 = 1;
+    pub const SIGINT: i64 = 2;
+    pub const SIGQUIT: i64 = 3;
+    pub const SIGABRT: i64 = 6;
+    pub const SIGUSR1: i64 = 10;
+    pub const SIGUSR2: i64 = 12;
+    pub const SIGPIPE: i64 = 13;
+    pub const SIGALRM: i64 = 14;
+    pub const SIGTERM: i64 = 15;
+    pub const SIGCHLD: i64 = 17;
+    /// restart interrupted syscalls instead of failing them with ``EINTR``
+    pub const SA_RESTART: i64 = 0x10000000;
+    /// pass ``siginfo_t *`` in rsi and the ``ucontext_t *`` in rdx to the handler
+    pub const SA_SIGINFO: i64 = 4;
+    /// do not block the signal while its handler runs
+    pub const SA_NODEFER: i64 = 0x40000000;
+    /// reset the handler to the default when the signal arrives
+    pub const SA_RESETHAND: i64 = 0x80000000;
+    const SA_RESTORER: i64 = 0x04000000;
+    pub const SIG_BLOCK: i64 = 0;
+    pub const SIG_UNBLOCK: i64 = 1;
+    pub const SIG_SETMASK: i64 = 2;
+
+    /// installs ``handler`` (a label, or 0/1 for the default/ignore) for ``signal`` with a
+    /// ``struct sigaction`` in ``.rodata``: handler, flags, restorer and an empty mask
+    fn sigaction(&mut self, signal: i64, handler: &str, flags: i64) {
+        self.use_routine(&SIGRETURN);
+        let label: &'static str = Box::leak(format!("xstd_sigaction_{}", self.parent.next_label_id()).into_boxed_str());
+        let line = format!("{}: dq {}, {:#x}, xstd_sigreturn, 0", label, handler, flags | Self::SA_RESTORER);
+        self.parent.add_readonly_variable(Variables::AsIs(Box::leak(line.into_boxed_str())), "");
+        self.parent.emit(Instruction::MovImm { dst: Register::rdi, imm: signal });
+        self.parent.emit(Instruction::MovIntoVar { reg: Register::rsi, var_name: label });
+        self.parent.emit(Instruction::AsIs("xor edx, edx"));
+        self.parent.emit(Instruction::MovImm { dst: Register::r10, imm: 8 });
+        self.syscall(13);
+    }
+
+    /// runs the function ``handler`` when ``signal`` arrives. the handler gets the signal number in rdi,
+    /// must preserve rbx, rbp and r12-r15 and return with ``ret``. ``flags`` takes ``SA_*``, usually ``SA_RESTART``
+    pub fn xsigaction(&mut self, signal: i64, handler: &'static str, flags: i64) {
+        if !self.parent.has_func(handler) {
+            panic!("xsigaction: `{}` is not a function, add it before installing it as a handler", handler);
+        }
+        self.sigaction(signal, handler, flags);
+    }
+
+    /// flushes stdout and exits with 128 + the signal number when ``signal`` arrives
+    pub fn xsigaction_exit(&mut self, signal: i64) {
+        self.use_routine(&SIG_EXIT);
+        self.sigaction(signal, "xstd_sig_exit", 0);
+    }
+
+    /// ignores ``signal`` from now on
+    pub fn xsigignore(&mut self, signal: i64) {
+        self.sigaction(signal, "1", 0);
+    }
+
+    /// restores the default action of ``signal``
+    pub fn xsigdefault(&mut self, signal: i64) {
+        self.sigaction(signal, "0", 0);
+    }
+
+    /// changes the blocked signals, ``how`` is one of ``SIG_BLOCK``, ``SIG_UNBLOCK`` and ``SIG_SETMASK``.
+    /// blocked signals stay pending until they are unblocked
+    pub fn xsigprocmask(&mut self, how: i64, signals: &[i64]) {
+        let mask = signals.iter().fold(0u64, |mask, &signal| {
+            if !(1..=64).contains(&signal) {
+                panic!("xsigprocmask: {} is not a signal number (1 to 64)", signal);
+            }
+            mask | 1 << (signal - 1)
+        });
+        self.parent.emit(Instruction::MovImm { dst: Register::rax, imm: mask as i64 });
+        self.parent.emit(Instruction::Push { reg: Register::rax });
+        self.parent.emit(Instruction::MovImm { dst: Register::rdi, imm: how });
+        self.parent.emit(Instruction::Mov { dst: Register::rsi, src: Register::rsp });
+        self.parent.emit(Instruction::AsIs("xor edx, edx"));
+        self.parent.emit(Instruction::MovImm { dst: Register::r10, imm: 8 });
+        self.syscall(14);
+        self.parent.emit(Instruction::AddImm { dst: Register::rsp, imm: 8 });
+    }
+
+    pub fn xsigblock(&mut self, signals: &[i64]) {
+        self.xsigprocmask(Self::SIG_BLOCK, signals);
+    }
+
+    pub fn xsigunblock(&mut self, signals: &[i64]) {
+        self.xsigprocmask(Self::SIG_UNBLOCK, signals);
+    }
+
+    /// sends ``signal`` to this process
+    pub fn xraise(&mut self, signal: i64) {
+        self.syscall(39);
+        self.parent.emit(Instruction::Mov { dst: Register::rdi, src: Register::rax });
+        self.parent.emit(Instruction::MovImm { dst: Register::rsi, imm: signal });
+        self.syscall(62);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{
+    init::{Register, Variables},
+    instructions::Instruction,
+    xstd::Xstd,
+};
+
+/// field access on ``Variables::Struct``, a field is named by its path (``pos.x`` for nested structs)
+impl<'a> Xstd<'a> {
+    fn struct_field(&self, var: &'static str, path: &str) -> (usize, Variables) {
+        let layout = match self.parent.get_variable(var) {
+            Some(Variables::Struct { layout, .. }) => layout,
+            _ => panic!("xstd: `{}` is not a struct", var),
+        };
+        match layout.field(path) {
+            Some(field) => field,
+            None => panic!("xstd: struct `{}` has no field `{}`", layout.name, path),
+        }
+    }
+
+    /// address expression ``var + offset`` of a field, for ``MovIntoVar``/``LeaIntoVar`` or as the base
+    /// of an array or struct field
+    pub fn xfield_addr(&self, var: &'static str, path: &str) -> &'static str {
+        let (offset, _) = self.struct_field(var, path);
+        Box::leak(format!("{} + {}", var, offset).into_boxed_str())
+    }
+
+    /// sized memory operand (``dword [var + offset]``) of a scalar field, for ``AsIs`` instructions
+    pub fn xfield(&self, var: &'static str, path: &str) -> &'static str {
+        let (offset, kind) = self.struct_field(var, path);
+        let size = match kind.size() {
+            1 => "byte",
+            2 => "word",
+            4 => "dword",
+            8 => "qword",
+            _ => panic!("xfield: `{}.{}` is not a scalar field", var, path),
+        };
+        Box::leak(format!("{} [{} + {}]", size, var, offset).into_boxed_str())
+    }
+
+    /// loads a scalar field into ``dst``, sign or zero extended to 64 bits. floats are loaded as their bit pattern
+    pub fn xload_field(&mut self, dst: Register, var: &'static str, path: &str) {
+        let kind = match self.struct_field(var, path).1 {
+            Variables::F32(_) => Variables::U32(0),
+            Variables::F64(_) => Variables::U64(0),
+            kind if kind.is_integer() || matches!(kind, Variables::Bool(_) | Variables::Ptr { .. }) => kind,
+            _ => panic!("xload_field: `{}.{}` is not a scalar field", var, path),
+        };
+        let addr = self.xfield_addr(var, path);
+        self.load_var(dst, addr, kind);
+    }
+
+    /// stores the low bytes of ``src`` into a scalar field
+    pub fn xstore_field(&mut self, var: &'static str, path: &str, src: Register) {
+        let store = format!("mov {}, {}", self.xfield(var, path), src.sized(self.struct_field(var, path).1.size()));
+        self.parent.emit(Instruction::AsIs(Box::leak(store.into_boxed_str())));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        asm_makers::linx8664::mk_asm_linx8664,
+        init::{LinuxX8664, Register, StructLayout, Variables},
+        xstd::Xstd,
+    };
+
+    static POINT: StructLayout = StructLayout {
+        name: "point",
+        fields: &[("tag", Variables::U8(0)), ("x", Variables::I32(0)), ("y", Variables::F64(0.0))],
+    };
+    static ENTITY: StructLayout = StructLayout {
+        name: "entity",
+        fields: &[("id", Variables::U16(0)), ("pos", Variables::Struct { layout: &POINT, init: &[] })],
+    };
+
+    #[test]
+    fn fields_are_addressed_at_their_aligned_offsets() {
+        let mut x = LinuxX8664::new();
+        x.add_mutable_variable(Variables::Struct { layout: &ENTITY, init: &[] }, "e");
+        let mut s = Xstd::new(&mut x);
+        assert_eq!(s.xfield("e", "id"), "word [e + 0]");
+        assert_eq!(s.xfield_addr("e", "pos"), "e + 8");
+        assert_eq!(s.xfield("e", "pos.x"), "dword [e + 12]");
+        assert_eq!(s.xfield("e", "pos.y"), "qword [e + 16]");
+        s.xload_field(Register::rax, "e", "pos.x");
+        s.xstore_field("e", "pos.tag", Register::rcx);
+        let asm = mk_asm_linx8664(&x);
+        assert!(asm.contains("movsxd rax, dword [e + 12]"), "{}", asm);
+        assert!(asm.contains("mov byte [e + 8], cl"), "{}", asm);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a scalar field")]
+    fn nested_structs_are_not_scalar_fields() {
+        let mut x = LinuxX8664::new();
+        x.add_variable(Variables::Struct { layout: &ENTITY, init: &[] }, "e");
+        Xstd::new(&mut x).xfield("e", "pos");
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{
+    init::{ArrayInit, ElemType, Register, Variables},
+    instructions::{Condition, Instruction},
+    xstd::{SwitchCase, Xstd},
+};
+
+/// switches with at most this many cases are a plain compare chain, as are the leaves of a search tree
+const CHAIN_MAX_CASES: usize = 4;
+/// a jump table is used when at least this percentage of the values between the lowest and the
+/// highest case have a case
+const TABLE_MIN_DENSITY: u128 = 40;
+const TABLE_MAX_LEN: u128 = 4096;
+
+impl<'a> Xstd<'a> {
+    /// compares ``reg`` with an immediate, going through r11 when it does not fit a sign extended 32 bit value
+    pub(crate) fn cmp_imm(&mut self, reg: Register, imm: i64) {
+        if i32::try_from(imm).is_ok() {
+            let cmp = format!("cmp {:?}, {}", reg, imm);
+            self.parent.emit(Instruction::AsIs(Box::leak(cmp.into_boxed_str())));
+        } else {
+            self.parent.emit(Instruction::MovImm { dst: Register::r11, imm });
+            self.parent.emit(Instruction::Cmp { op1: reg, op2: Register::r11 });
+        }
+    }
+
+    fn switch_chain(&mut self, value: Register, cases: &[(i64, String)], default: &str) {
+        for (case, target) in cases {
+            self.cmp_imm(value, *case);
+            self.parent.emit(Instruction::Je(target.clone()));
+        }
+        self.parent.emit(Instruction::Jmp(default.to_string()));
+    }
+
+    /// binary search on the sorted cases with signed compares, small ranges end in a compare chain
+    fn switch_tree(&mut self, value: Register, cases: &[(i64, String)], default: &str, id: usize) {
+        if cases.len() <= CHAIN_MAX_CASES {
+            self.switch_chain(value, cases, default);
+            return;
+        }
+        let mid = cases.len() / 2;
+        let lower = format!("switch_{}_below_{}", id, cases[mid].0).replace('-', "m");
+        self.cmp_imm(value, cases[mid].0);
+        self.parent.emit(Instruction::Je(cases[mid].1.clone()));
+        self.parent.emit(Instruction::Jl(lower.clone()));
+        self.switch_tree(value, &cases[mid + 1..], default, id);
+        self.parent.emit(Instruction::Label(lower));
+        self.switch_tree(value, &cases[..mid], default, id);
+    }
+
+    /// indirect jump through a table of addresses in ``.rodata``, values without a case lead to ``default``
+    fn switch_table(&mut self, value: Register, cases: &[(i64, String)], default: &str, id: usize) {
+        let min = cases[0].0;
+        let len = (cases[cases.len() - 1].0 as i128 - min as i128 + 1) as usize;
+        let mut targets: Vec<&'static str> = vec![Box::leak(default.to_string().into_boxed_str()); len];
+        for (case, target) in cases {
+            targets[(*case as i128 - min as i128) as usize] = Box::leak(target.clone().into_boxed_str());
+        }
+        let table: &'static str = Box::leak(format!("switch_{}_table", id).into_boxed_str());
+        let init = ArrayInit::Ptrs(Box::leak(targets.into_boxed_slice()));
+        self.parent.add_readonly_variable(Variables::Array { elem: ElemType::U64, len, init }, table);
+        // r11 = value - min, values below min wrap around and fail the unsigned bound check
+        if i32::try_from(min).is_ok() {
+            self.parent.emit(Instruction::Mov { dst: Register::r11, src: value });
+            if min != 0 {
+                let sub = format!("sub r11, {}", min);
+                self.parent.emit(Instruction::AsIs(Box::leak(sub.into_boxed_str())));
+            }
+        } else {
+            self.parent.emit(Instruction::MovImm { dst: Register::r11, imm: min });
+            self.parent.emit(Instruction::Sub { dst: Register::r11, src: value });
+            self.parent.emit(Instruction::AsIs("neg r11"));
+        }
+        self.cmp_imm(Register::r11, len as i64 - 1);
+        self.parent.emit(Instruction::Jcc { cond: Condition::Above, label: default.to_string() });
+        let jump = format!("jmp [{} + r11 * 8]", table);
+        self.parent.emit(Instruction::AsIs(Box::leak(jump.into_boxed_str())));
+    }
+
+    /// jumps to the case matching the signed value in ``value`` (like a rust ``match``, without fallthrough),
+    /// or to ``default`` when no case matches. ``None`` as the default continues after the switch.
+    /// a few cases become a compare chain, dense cases a jump table in ``.rodata`` and sparse ones a
+    /// binary search. clobbers r11
+    pub fn xswitch(&mut self, value: Register, cases: Vec<(i64, SwitchCase<'_>)>, default: Option<SwitchCase<'_>>) {
+        if value == Register::r11 {
+            panic!("xswitch: the value can not be in r11, it is used as scratch");
+        }
+        let id = self.parent.next_label_id();
+        let end = format!("switch_{}_end", id);
+        let mut cases = cases;
+        cases.sort_by_key(|(case, _)| *case);
+        if let Some(pair) = cases.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            panic!("xswitch: the case {} is listed twice", pair[0].0);
+        }
+        let targets: Vec<(i64, String)> = cases
+            .iter()
+            .enumerate()
+            .map(|(i, (case, target))| match target {
+                SwitchCase::Jump(label) => (*case, label.to_string()),
+                SwitchCase::Body(_) => (*case, format!("switch_{}_case_{}", id, i)),
+            })
+            .collect();
+        let default_target = match &default {
+            Some(SwitchCase::Jump(label)) => label.to_string(),
+            Some(SwitchCase::Body(_)) => format!("switch_{}_default", id),
+            None => end.clone(),
+        };
+        if targets.len() <= CHAIN_MAX_CASES {
+            self.switch_chain(value, &targets, &default_target);
+        } else {
+            let span = (targets[targets.len() - 1].0 as i128 - targets[0].0 as i128 + 1) as u128;
+            if span <= TABLE_MAX_LEN && targets.len() as u128 * 100 >= span * TABLE_MIN_DENSITY {
+                self.switch_table(value, &targets, &default_target, id);
+            } else {
+                self.switch_tree(value, &targets, &default_target, id);
+            }
+        }
+        for ((_, target), (_, label)) in cases.into_iter().zip(targets) {
+            if let SwitchCase::Body(body) = target {
+                self.parent.emit(Instruction::Label(label));
+                body(self);
+                self.parent.emit(Instruction::Jmp(end.clone()));
+            }
+        }
+        if let Some(SwitchCase::Body(body)) = default {
+            self.parent.emit(Instruction::Label(default_target));
+            body(self);
+        }
+        self.parent.emit(Instruction::Label(end));
+    }
+}
@@ -0,0 +1,228 @@
+use crate::{
+    init::Register,
+    instructions::Instruction,
+    xstd::{Operand, Routine, Xstd},
+};
+
+// every thread gets its own 1 MiB mapping. the first 4 bytes hold the thread id, which the kernel
+// writes on clone (CLONE_PARENT_SETTID) and clears with a futex wake when the thread exits
+// (CLONE_CHILD_CLEARTID). the stack grows down from the end of the mapping, the mapping address is the
+// handle used for joining
+
+/// starts a thread running the function rdi with rsi as its argument in rdi.
+/// returns the thread handle in rax or ``-errno``
+static THREAD_SPAWN: Routine = Routine {
+    name: "xstd_thread_spawn",
+    args: &[Register::rdi, Register::rsi],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    push rdi
+    push rsi
+    xor edi, edi
+    mov esi, 1048576
+    mov edx, 3
+    mov r10d, 0x20022
+    mov r8, -1
+    xor r9d, r9d
+    mov eax, 9
+    syscall
+    pop rsi
+    pop rdi
+    cmp rax, -4096
+    ja .done
+    lea r9, [rax + 1048576 - 16]
+    mov [r9 + 8], rdi
+    mov [r9], rsi
+    mov rdx, rax
+    mov r10, rax
+    push rax
+    mov edi, 0x350f00
+    mov rsi, r9
+    xor r8d, r8d
+    mov eax, 56
+    syscall
+    test rax, rax
+    jz .child
+    pop rdi
+    test rax, rax
+    js .failed
+    mov rax, rdi
+    .done:
+    ret
+    .failed:
+    push rax
+    mov esi, 1048576
+    mov eax, 11
+    syscall
+    pop rax
+    ret
+    .child:
+    pop rdi
+    pop rax
+    call rax
+    xor edi, edi
+    mov eax, 60
+    syscall
+",
+};
+
+/// waits until the thread with the handle rdi has exited and frees its stack
+static THREAD_JOIN: Routine = Routine {
+    name: "xstd_thread_join",
+    args: &[Register::rdi],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    .wait:
+    mov edx, [rdi]
+    test edx, edx
+    jz .done
+    xor esi, esi
+    xor r10d, r10d
+    mov eax, 202
+    syscall
+    jmp .wait
+    .done:
+    mov esi, 1048576
+    mov eax, 11
+    syscall
+    ret
+",
+};
+
+/// locks the mutex (a dword: 0 unlocked, 1 locked, 2 locked with waiters) at rdi
+static MUTEX_LOCK: Routine = Routine {
+    name: "xstd_mutex_lock",
+    args: &[Register::rdi],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    xor eax, eax
+    mov ecx, 1
+    lock cmpxchg [rdi], ecx
+    jz .done
+    .contended:
+    mov ecx, 2
+    xchg [rdi], ecx
+    test ecx, ecx
+    jz .done
+    mov esi, 128
+    mov edx, 2
+    xor r10d, r10d
+    mov eax, 202
+    syscall
+    jmp .contended
+    .done:
+    ret
+",
+};
+
+/// unlocks the mutex at rdi, waking one waiter if there are any
+static MUTEX_UNLOCK: Routine = Routine {
+    name: "xstd_mutex_unlock",
+    args: &[Register::rdi],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    lock dec dword [rdi]
+    jz .done
+    mov dword [rdi], 0
+    mov esi, 129
+    mov edx, 1
+    mov eax, 202
+    syscall
+    .done:
+    ret
+",
+};
+
+impl<'a> Xstd<'a> {
+    /// size of the stack mapping every thread gets
+    pub const THREAD_STACK_SIZE: i64 = 1 << 20;
+
+    /// starts a thread running the function ``func`` with ``arg`` in rdi and stores its handle in the
+    /// 64 bit variable ``handle``. rax holds the handle or ``-errno``. the thread ends when ``func``
+    /// returns, results are passed back through variables. ``xexit`` ends all threads.
+    /// the stdout buffer is shared, hold a mutex around printing from several threads
+    pub fn xthread_spawn(&mut self, func: &'static str, arg: Operand, handle: &'static str) {
+        if !self.parent.has_func(func) {
+            panic!("xthread_spawn: `{}` is not a function, add it before spawning it", func);
+        }
+        self.parent.emit(Instruction::MovIntoVar { reg: Register::rdi, var_name: func });
+        self.load_args(&[(Register::rsi, arg)]);
+        self.call_routine(&THREAD_SPAWN);
+        self.store_var(handle, Register::rax);
+    }
+
+    /// waits for the thread whose handle is in the variable ``handle`` to end
+    pub fn xthread_join(&mut self, handle: &'static str) {
+        self.load_args(&[(Register::rdi, Operand::Var(handle))]);
+        self.call_routine(&THREAD_JOIN);
+    }
+
+    fn sized_var(&self, var: &'static str, size: usize, helper: &str) {
+        match self.parent.get_variable(var) {
+            Some(kind) if kind.is_integer() && kind.size() == size => {}
+            _ => panic!("{}: `{}` is not a {} bit integer variable", helper, var, size * 8),
+        }
+    }
+
+    /// locks the mutex ``var``, a ``U32`` variable starting at 0, sleeping while another thread holds it
+    pub fn xmutex_lock(&mut self, var: &'static str) {
+        self.sized_var(var, 4, "xmutex_lock");
+        self.parent.emit(Instruction::MovIntoVar { reg: Register::rdi, var_name: var });
+        self.call_routine(&MUTEX_LOCK);
+    }
+
+    pub fn xmutex_unlock(&mut self, var: &'static str) {
+        self.sized_var(var, 4, "xmutex_unlock");
+        self.parent.emit(Instruction::MovIntoVar { reg: Register::rdi, var_name: var });
+        self.call_routine(&MUTEX_UNLOCK);
+    }
+
+    /// atomically adds ``value`` to the 64 bit variable ``var``, ``dst`` receives the old value. clobbers r11
+    pub fn xatomic_fetch_add(&mut self, var: &'static str, value: Operand, dst: Register) {
+        self.sized_var(var, 8, "xatomic_fetch_add");
+        if dst == Register::r11 {
+            panic!("xatomic_fetch_add: dst can not be r11, it holds the address of `{}`", var);
+        }
+        self.load_args(&[(dst, value)]);
+        self.parent.emit(Instruction::MovIntoVar { reg: Register::r11, var_name: var });
+        self.parent.emit(Instruction::LockXadd { addr: Register::r11, src: dst });
+    }
+
+    /// atomically stores ``new`` into the 64 bit variable ``var`` if it holds ``expected``. rax receives
+    /// the old value and ZF is set if the store happened (``je`` on success). clobbers r11
+    pub fn xatomic_cas(&mut self, var: &'static str, expected: Operand, new: Register) {
+        self.sized_var(var, 8, "xatomic_cas");
+        if new == Register::rax {
+            panic!("xatomic_cas: the new value can not be in rax, it holds the expected value");
+        }
+        if new == Register::r11 {
+            panic!("xatomic_cas: the new value can not be in r11, it holds the address of `{}`", var);
+        }
+        self.load_args(&[(Register::rax, expected)]);
+        self.parent.emit(Instruction::MovIntoVar { reg: Register::r11, var_name: var });
+        self.parent.emit(Instruction::LockCmpxchg { addr: Register::r11, src: new });
+    }
+
+    /// atomically swaps ``reg`` with the 64 bit variable ``var``. clobbers r11
+    pub fn xatomic_swap(&mut self, var: &'static str, reg: Register) {
+        self.sized_var(var, 8, "xatomic_swap");
+        if reg == Register::r11 {
+            panic!("xatomic_swap: reg can not be r11, it holds the address of `{}`", var);
+        }
+        self.parent.emit(Instruction::MovIntoVar { reg: Register::r11, var_name: var });
+        self.parent.emit(Instruction::Xchg { addr: Register::r11, src: reg });
+    }
+
+    /// orders every load and store before it against every one after it
+    pub fn xmfence(&mut self) {
+        self.parent.emit(Instruction::Mfence);
+    }
+}
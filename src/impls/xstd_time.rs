@@ -0,0 +1,186 @@
+use crate::{
+    impls::xstd_out::WRITE,
+    init::{Register, Variables},
+    instructions::Instruction,
+    xstd::{Operand, Routine, Xstd, PRINT_STR, PRINT_UINT},
+};
+
+/// reads the clock rdi, returns nanoseconds in rax or ``-errno``
+static CLOCK_NS: Routine = Routine {
+    name: "xstd_clock_ns",
+    args: &[Register::rdi],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    sub rsp, 16
+    mov rsi, rsp
+    mov eax, 228
+    syscall
+    test rax, rax
+    js .done
+    imul rax, [rsp], 1000000000
+    add rax, [rsp + 8]
+    .done:
+    add rsp, 16
+    ret
+",
+};
+
+/// sleeps for rdi nanoseconds, sleeping again for the rest when a signal interrupts it
+static SLEEP_NS: Routine = Routine {
+    name: "xstd_sleep_ns",
+    args: &[Register::rdi],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    mov rax, rdi
+    xor edx, edx
+    mov ecx, 1000000000
+    div rcx
+    push rdx
+    push rax
+    .again:
+    mov rdi, rsp
+    mov rsi, rsp
+    mov eax, 35
+    syscall
+    cmp rax, -4
+    je .again
+    add rsp, 16
+    ret
+",
+};
+
+/// prints the nanoseconds in rdi with the largest fitting unit out of ns, us, ms and s,
+/// with 3 decimals unless the unit is ns
+static PRINT_ELAPSED: Routine = Routine {
+    name: "xstd_print_elapsed",
+    args: &[Register::rdi],
+    deps: &[&PRINT_UINT, &PRINT_STR, &WRITE],
+    rodata: &[
+        ("xstd_unit_ns", Variables::Str(" ns")),
+        ("xstd_unit_us", Variables::Str(" us")),
+        ("xstd_unit_ms", Variables::Str(" ms")),
+        ("xstd_unit_s", Variables::Str(" s")),
+    ],
+    bss: &[],
+    body: "
+    push rbx
+    push r12
+    push r13
+    mov rbx, rdi
+    mov r13, xstd_unit_ns
+    cmp rbx, 1000
+    jb .whole
+    mov r12d, 1000
+    mov r13, xstd_unit_us
+    cmp rbx, 1000000
+    jb .scaled
+    mov r12d, 1000000
+    mov r13, xstd_unit_ms
+    cmp rbx, 1000000000
+    jb .scaled
+    mov r12d, 1000000000
+    mov r13, xstd_unit_s
+    .scaled:
+    mov rax, rbx
+    xor edx, edx
+    div r12
+    mov rbx, rdx
+    mov rdi, rax
+    mov esi, 10
+    call xstd_print_uint
+    imul rax, rbx, 1000
+    xor edx, edx
+    div r12
+    mov ecx, 10
+    sub rsp, 8
+    mov byte [rsp], '.'
+    xor edx, edx
+    div rcx
+    add dl, '0'
+    mov [rsp + 3], dl
+    xor edx, edx
+    div rcx
+    add dl, '0'
+    mov [rsp + 2], dl
+    add al, '0'
+    mov [rsp + 1], al
+    mov rsi, rsp
+    mov edx, 4
+    call xstd_write
+    add rsp, 8
+    jmp .unit
+    .whole:
+    mov rdi, rbx
+    mov esi, 10
+    call xstd_print_uint
+    .unit:
+    mov rdi, r13
+    pop r13
+    pop r12
+    pop rbx
+    jmp xstd_print_str
+",
+};
+
+/// time helpers. clock readings are nanoseconds in a single register, so two readings of the same
+/// clock can simply be subtracted
+impl<'a> Xstd<'a> {
+    /// wall clock time since the unix epoch, can jump when the system time is changed
+    pub const CLOCK_REALTIME: i64 = 0;
+    /// time since an arbitrary point that never goes backwards, use this for measuring
+    pub const CLOCK_MONOTONIC: i64 = 1;
+
+    /// reads ``clock`` (see ``CLOCK_*``) as nanoseconds into ``dst``
+    pub fn xclock_gettime(&mut self, clock: i64, dst: Register) {
+        self.parent.emit(Instruction::MovImm { dst: Register::rdi, imm: clock });
+        self.call_routine(&CLOCK_NS);
+        if dst != Register::rax {
+            self.parent.emit(Instruction::Mov { dst, src: Register::rax });
+        }
+    }
+
+    /// reads ``clock`` as nanoseconds into the integer variable ``var``, usually a ``U64`` in ``.bss``
+    pub fn xclock_gettime_into(&mut self, clock: i64, var: &'static str) {
+        self.xclock_gettime(clock, Register::rax);
+        self.store_var(var, Register::rax);
+    }
+
+    /// sleeps for ``ns`` nanoseconds
+    pub fn xnanosleep(&mut self, ns: Operand) {
+        self.load_args(&[(Register::rdi, ns)]);
+        self.call_routine(&SLEEP_NS);
+    }
+
+    /// reads the time stamp counter into ``dst`` after all earlier instructions have finished, clobbers rdx
+    pub fn xrdtsc(&mut self, dst: Register) {
+        self.parent.emit(Instruction::AsIs("lfence"));
+        self.parent.emit(Instruction::AsIs("rdtsc"));
+        self.tsc_result(dst);
+    }
+
+    /// like ``xrdtsc`` but with rdtscp, which waits for earlier instructions on its own and also stores
+    /// the cpu id in ecx. clobbers rcx and rdx
+    pub fn xrdtscp(&mut self, dst: Register) {
+        self.parent.emit(Instruction::AsIs("rdtscp"));
+        self.tsc_result(dst);
+    }
+
+    fn tsc_result(&mut self, dst: Register) {
+        self.parent.emit(Instruction::AsIs("shl rdx, 32"));
+        self.parent.emit(Instruction::Or { dst: Register::rax, src: Register::rdx });
+        if dst != Register::rax {
+            self.parent.emit(Instruction::Mov { dst, src: Register::rax });
+        }
+    }
+
+    /// prints the time between two nanosecond readings of the same clock, like ``1.250 ms``
+    pub fn xprint_elapsed(&mut self, start: Operand, end: Operand) {
+        self.load_args(&[(Register::rdi, end), (Register::rsi, start)]);
+        self.parent.emit(Instruction::Sub { dst: Register::rdi, src: Register::rsi });
+        self.call_routine(&PRINT_ELAPSED);
+    }
+}
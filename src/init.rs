@@ -1,7 +1,5 @@
-use rand::Rng;
-
 use crate::instructions::Instruction;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
 
 #[allow(non_camel_case_types)]
@@ -25,12 +23,39 @@ pub enum Register {
     r15,
 }
 
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XmmRegister {
+    xmm0,
+    xmm1,
+    xmm2,
+    xmm3,
+    xmm4,
+    xmm5,
+    xmm6,
+    xmm7,
+    xmm8,
+    xmm9,
+    xmm10,
+    xmm11,
+    xmm12,
+    xmm13,
+    xmm14,
+    xmm15,
+}
+
 #[derive(Debug)]
 pub struct RegisterAllocator {
     free_regs: VecDeque<Register>,
     used_regs: Vec<Register>,
 }
 
+impl Default for RegisterAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RegisterAllocator {
     pub fn new() -> Self {
         let free_regs = VecDeque::from(vec![
@@ -159,6 +184,13 @@ impl fmt::Display for Instruction {
                 MovToMem { src, addr } => write!(f, "mov [{:?}], {:?}", addr, src),
                 MovFromMem { addr, dst } => write!(f, "mov {:?}, [{:?}]", dst, addr),
                 AddImm { dst, imm } => write!(f, "add {:?}, {}", dst, imm),
+                LockXadd { addr, src } => write!(f, "lock xadd [{:?}], {:?}", addr, src),
+                LockCmpxchg { addr, src } => write!(f, "lock cmpxchg [{:?}], {:?}", addr, src),
+                Xchg { addr, src } => write!(f, "xchg [{:?}], {:?}", addr, src),
+                Mfence => write!(f, "mfence"),
+                Jcc { cond, label } => write!(f, "j{} {}", cond.suffix(), label),
+                Setcc { cond, dst } => write!(f, "set{} {}", cond.suffix(), dst.byte()),
+                Cmovcc { cond, dst, src } => write!(f, "cmov{} {:?}, {:?}", cond.suffix(), dst, src),
                 AsIs(s) => write!(f, "{}", s),
                 SYSCALL => write!(f, "syscall"),
                 LeaIntoVar {reg,var_name} => write!(f, "lea {:?}, [{}]", reg, var_name),
@@ -181,11 +213,80 @@ pub enum Variables {
     F64(f64),
     Bool(bool),
     Str(&'static str),
+    /// raw bytes written as they are, without a trailing 0. for binary blobs and text written by length,
+    /// ``Bytes(b"hi\n")`` or ``Bytes(b"ok\0")`` for an explicit terminator
+    Bytes(&'static [u8]),
+    /// the 64 bit address of another variable, function or label plus ``offset`` (``dq target + offset``)
+    Ptr { target: &'static str, offset: i64 },
     ///as is value , no changes take place , plain str writter directly no converion
     /// write entire line of assembly , no need to provide a name for variable when using ``.add_variable``
     AsIs(&'static str),
+    /// ``len`` elements of type ``elem``, laid out back to back. ``init`` is ignored in ``.bss``, which is always zeroed
+    Array { elem: ElemType, len: usize, init: ArrayInit },
+    /// a record laid out by ``layout``. ``init`` replaces the field values of the layout when it is not empty,
+    /// it is ignored in ``.bss``
+    Struct { layout: &'static StructLayout, init: &'static [Variables] },
+}
+
+/// a record type for ``Variables::Struct``. fields are laid out in order, each at its natural alignment as in
+/// the System V ABI, and the size is rounded up to the largest alignment. the field values are the initial
+/// contents in ``.data``/``.rodata``
+#[derive(Debug, PartialEq)]
+pub struct StructLayout {
+    pub name: &'static str,
+    pub fields: &'static [(&'static str, Variables)],
 }
 
+/// element type of a ``Variables::Array``
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ElemType {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    Bool,
+}
+
+/// initial contents of a ``Variables::Array`` in ``.data``/``.rodata``
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ArrayInit {
+    /// every element is 0
+    Zero,
+    /// every element holds the value (``times len dd value``)
+    Fill(i64),
+    /// the listed values, elements past the end of the list are 0
+    List(&'static [i64]),
+    /// like ``Fill`` for ``F32``/``F64`` elements
+    FillFloat(f64),
+    /// like ``List`` for ``F32``/``F64`` elements
+    ListFloat(&'static [f64]),
+    /// addresses of the listed variables, functions or labels for ``I64``/``U64`` elements,
+    /// elements past the end of the list are null
+    Ptrs(&'static [&'static str]),
+}
+
+/// a named section for ``place_in_section``. ``flags`` are nasm section attributes such as
+/// ``progbits alloc noexec write align=16``, variables from ``direct_add_mut_var`` need ``nobits``
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Section {
+    pub name: &'static str,
+    pub flags: &'static str,
+}
+
+/// instructions, variables, mutable variables and functions as handed out by ``.dump()``
+pub type Dump<'a> = (
+    &'a [Instruction],
+    &'a [(&'static str, Variables)],
+    &'a [(&'static str, Variables)],
+    &'a [Funcs],
+);
+
 #[derive(Debug)]
 struct XasmCore {
     instructions: Vec<Instruction>,
@@ -194,6 +295,16 @@ struct XasmCore {
     variables: Vec<(&'static str, Variables)>,
     mutable_variables: Vec<(&'static str, Variables)>,
     funcs : Vec<Funcs>,
+    startup: Vec<Instruction>,
+    readonly_variables: Vec<(&'static str, Variables)>,
+    sections: Vec<(&'static str, Section)>,
+    aligns: Vec<(&'static str, usize)>,
+    pooled_strings: Vec<(&'static str, Variables)>,
+    pooled_constants: Vec<(&'static str, Variables)>,
+    /// label of every pooled value by its kind and bytes, strings have kind 0 and constants their size
+    pool_index: HashMap<(usize, Vec<u8>), &'static str>,
+    /// the next id handed out by ``next_label_id``
+    label_count: usize,
 }
 
 #[derive(Debug)]
@@ -221,6 +332,14 @@ impl XasmCore {
             variables: Vec::new(),
             mutable_variables: Vec::new(),
             funcs : Vec::new(),
+            startup: Vec::new(),
+            readonly_variables: Vec::new(),
+            sections: Vec::new(),
+            aligns: Vec::new(),
+            pooled_strings: Vec::new(),
+            pooled_constants: Vec::new(),
+            pool_index: HashMap::new(),
+            label_count: 0,
         }
     }
 
@@ -228,6 +347,15 @@ impl XasmCore {
         self.instructions.push(instr);
     }
 
+    fn next_label_id(&mut self) -> usize {
+        self.label_count += 1;
+        self.label_count - 1
+    }
+
+    fn emit_startup(&mut self, instr: Instruction) {
+        self.startup.push(instr);
+    }
+
     fn alloc_reg(&mut self, force: bool) -> Register {
         let reg = self.reg_alloc.allocate(force);
         self.reg_stack.push(reg);
@@ -247,20 +375,129 @@ impl XasmCore {
         }
     }
 
-    fn dump(
-        &self,
-    ) -> (
-        &[Instruction],
-        &[(&'static str, Variables)],
-        &[(&'static str, Variables)],
-        &[Funcs],
-    ) {
+    fn dump(&self) -> Dump<'_> {
         (&self.instructions, &self.variables, &self.mutable_variables, &self.funcs)
     }
 
     fn add_variable(&mut self, var: Variables, name: &'static str) {
         self.variables.push((name, var));
     }
+
+    fn add_readonly_variable(&mut self, var: Variables, name: &'static str) {
+        self.readonly_variables.push((name, var));
+    }
+
+    fn is_readonly(&self, name: &str) -> bool {
+        self.readonly_variables
+            .iter()
+            .chain(self.pooled_strings.iter())
+            .chain(self.pooled_constants.iter())
+            .any(|(n, _)| *n == name)
+    }
+
+    fn pool_label(&self) -> &'static str {
+        let id = self.pooled_strings.len() + self.pooled_constants.len();
+        Box::leak(format!("pool_{}", id).into_boxed_str())
+    }
+
+    fn intern_str(&mut self, text: &str) -> &'static str {
+        let key = (0, text.as_bytes().to_vec());
+        if let Some(label) = self.pool_index.get(&key) {
+            return label;
+        }
+        let label = self.pool_label();
+        self.pooled_strings.push((label, Variables::Str(Box::leak(text.to_string().into_boxed_str()))));
+        self.pool_index.insert(key, label);
+        label
+    }
+
+    fn intern(&mut self, value: Variables) -> &'static str {
+        match value {
+            Variables::Str(text) => return self.intern_str(text),
+            Variables::AsIs(_)
+            | Variables::Bytes(_)
+            | Variables::Ptr { .. }
+            | Variables::Array { .. }
+            | Variables::Struct { .. } => {
+                panic!("intern: only scalars and strings can be pooled, not {:?}", value)
+            }
+            _ => {}
+        }
+        // constants are shared by their bytes, so I64(5) and U64(5) or two nan payloads compare by value
+        let key = (value.size(), value.bits().to_le_bytes()[..value.size()].to_vec());
+        if let Some(label) = self.pool_index.get(&key) {
+            return label;
+        }
+        let label = self.pool_label();
+        self.pooled_constants.push((label, value));
+        self.pool_index.insert(key, label);
+        label
+    }
+
+    fn place_in_section(&mut self, name: &'static str, section: Section) {
+        if self.get_variable(name).is_none() && !self.has_func(name) {
+            panic!("place_in_section: `{}` is not a variable or function, add it first", name);
+        }
+        self.sections.retain(|(n, _)| *n != name);
+        self.sections.push((name, section));
+    }
+
+    fn section_of(&self, name: &str) -> Option<Section> {
+        self.sections.iter().find(|(n, _)| *n == name).map(|(_, section)| *section)
+    }
+
+    fn set_align(&mut self, name: &'static str, align: usize) {
+        if !align.is_power_of_two() {
+            panic!("set_align: the alignment of `{}` must be a power of two, not {}", name, align);
+        }
+        if self.get_variable(name).is_none() && !self.has_func(name) {
+            panic!("set_align: `{}` is not a variable or function, add it first", name);
+        }
+        self.aligns.retain(|(n, _)| *n != name);
+        self.aligns.push((name, align));
+    }
+
+    fn align_of(&self, name: &str) -> Option<usize> {
+        self.aligns.iter().find(|(n, _)| *n == name).map(|(_, align)| *align)
+    }
+
+    fn get_variable(&self, name: &str) -> Option<Variables> {
+        self.variables
+            .iter()
+            .chain(self.readonly_variables.iter())
+            .chain(self.pooled_strings.iter())
+            .chain(self.pooled_constants.iter())
+            .chain(self.mutable_variables.iter())
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| *v)
+    }
+
+    fn has_func(&self, name: &str) -> bool {
+        self.funcs.iter().any(|f| f.name == name)
+    }
+
+    fn has_symbol(&self, name: &str) -> bool {
+        let defines = |code: &str| {
+            let code = code.trim_start();
+            code.strip_prefix(name).is_some_and(|rest| rest.trim_start().starts_with(':') || rest.starts_with(" equ "))
+        };
+        let is_label = |instr: &Instruction| match instr {
+            Instruction::Label(label) => label == name,
+            Instruction::AsIs(code) => code.lines().any(defines),
+            _ => false,
+        };
+        name == "_start"
+            || self.get_variable(name).is_some_and(|var| !matches!(var, Variables::AsIs(_)))
+            || self.has_func(name)
+            || self.instructions.iter().chain(self.startup.iter()).any(is_label)
+            || self.funcs.iter().flat_map(|f| f.body.iter()).any(is_label)
+            || self
+                .variables
+                .iter()
+                .chain(self.readonly_variables.iter())
+                .chain(self.mutable_variables.iter())
+                .any(|(_, var)| matches!(var, Variables::AsIs(code) if defines(code)))
+    }
 }
 #[derive(Debug)]
 
@@ -280,6 +517,16 @@ impl Xasm {
         self.core.emit(instr)
     }
 
+    fn emit_startup(&mut self, instr: Instruction) {
+        self.core.emit_startup(instr)
+    }
+    fn startup(&self) -> &[Instruction] {
+        &self.core.startup
+    }
+    fn readonly_variables(&self) -> &[(&'static str, Variables)] {
+        &self.core.readonly_variables
+    }
+
     fn alloc_reg(&mut self, force: bool) -> Register {
         self.core.alloc_reg(force)
     }
@@ -292,14 +539,7 @@ impl Xasm {
         self.core.free_reg(reg)
     }
 
-    fn dump(
-        &self,
-    ) -> (
-        &[Instruction],
-        &[(&'static str, Variables)],
-        &[(&'static str, Variables)],
-        &[Funcs],
-    ) {
+    fn dump(&self) -> Dump<'_> {
         self.core.dump()
     }
 
@@ -308,15 +548,18 @@ impl Xasm {
     }
     fn add_mutable_variable(&mut self, var: Variables, var_name: &'static str) {
         self.core.mutable_variables.push((var_name, var));
-        let tempname: &'static str = Box::leak(format!("temp_{}", rand::rng().random::<u32>()).into_boxed_str());
-        self.add_variable(var, tempname);
+        let tempname: &'static str = Box::leak(format!("temp_{}", self.core.next_label_id()).into_boxed_str());
+        self.core.add_readonly_variable(var, tempname);
         let free_reg = self.get_reg(Register::rcx, true);
-        self.emit(Instruction::MovIntoVar { var_name: &tempname, reg: Register::rsi });
-        self.emit(Instruction::MovIntoVar { var_name: var_name, reg: Register::rdi });
+        self.emit(Instruction::MovIntoVar { var_name: tempname, reg: Register::rsi });
+        self.emit(Instruction::MovIntoVar { var_name, reg: Register::rdi });
         match var {
-            Variables::Str(ref txt) => {
+            Variables::Str(txt) => {
                 self.emit(Instruction::MovImm { dst: free_reg, imm: txt.len() as i64 });
             }
+            Variables::Array { .. } | Variables::Struct { .. } | Variables::Bytes(_) | Variables::Ptr { .. } => {
+                self.emit(Instruction::MovImm { dst: free_reg, imm: var.size() as i64 });
+            }
             Variables::I8(val) => {
                 self.emit(Instruction::MovImm { dst: free_reg, imm: val as i64 });
                 self.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
@@ -330,7 +573,7 @@ impl Xasm {
                 self.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
             }
             Variables::I64(val) => {
-                self.emit(Instruction::MovImm { dst: free_reg, imm: val as i64 });
+                self.emit(Instruction::MovImm { dst: free_reg, imm: val });
                 self.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
             }
             Variables::U8(val) => {
@@ -361,7 +604,7 @@ impl Xasm {
                 self.emit(Instruction::MovF { dst: free_reg, imm: val });
                 self.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
             }
-            Variables::AsIs(ref txt) => {
+            Variables::AsIs(txt) => {
                 self.emit(Instruction::MovIntoVar { reg: free_reg, var_name: txt });
                 self.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
             }
@@ -377,6 +620,48 @@ impl Xasm {
     fn direct_add_mut_var(&mut self, var: Variables, var_name: &'static str) {
         self.core.mutable_variables.push((var_name, var));
     }
+    fn get_variable(&self, name: &str) -> Option<Variables> {
+        self.core.get_variable(name)
+    }
+    fn has_func(&self, name: &str) -> bool {
+        self.core.has_func(name)
+    }
+    fn add_readonly_variable(&mut self, var: Variables, name: &'static str) {
+        self.core.add_readonly_variable(var, name)
+    }
+    fn is_readonly(&self, name: &str) -> bool {
+        self.core.is_readonly(name)
+    }
+    fn place_in_section(&mut self, name: &'static str, section: Section) {
+        self.core.place_in_section(name, section)
+    }
+    fn section_of(&self, name: &str) -> Option<Section> {
+        self.core.section_of(name)
+    }
+    fn set_align(&mut self, name: &'static str, align: usize) {
+        self.core.set_align(name, align)
+    }
+    fn align_of(&self, name: &str) -> Option<usize> {
+        self.core.align_of(name)
+    }
+    fn has_symbol(&self, name: &str) -> bool {
+        self.core.has_symbol(name)
+    }
+    fn next_label_id(&mut self) -> usize {
+        self.core.next_label_id()
+    }
+    fn intern_str(&mut self, text: &str) -> &'static str {
+        self.core.intern_str(text)
+    }
+    fn intern(&mut self, value: Variables) -> &'static str {
+        self.core.intern(value)
+    }
+    fn pooled_strings(&self) -> &[(&'static str, Variables)] {
+        &self.core.pooled_strings
+    }
+    fn pooled_constants(&self) -> &[(&'static str, Variables)] {
+        &self.core.pooled_constants
+    }
 }
 
 #[derive(Debug)]
@@ -385,6 +670,12 @@ pub struct LinuxX8664 {
     parent: Xasm,
 }
 
+impl Default for LinuxX8664 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LinuxX8664 {
     pub fn new() -> Self {
         Self {
@@ -396,6 +687,20 @@ impl LinuxX8664 {
         self.parent.emit(instr);
     }
 
+    /// emits an instruction that runs at the very start of ``_start``, before everything passed to ``.emit``
+    /// while ``rsp`` still points at ``argc``
+    pub fn emit_startup(&mut self, instr: Instruction) {
+        self.parent.emit_startup(instr);
+    }
+    /// the instructions passed to ``.emit_startup``, in order
+    pub fn startup(&self) -> &[Instruction] {
+        self.parent.startup()
+    }
+    /// the variables added with ``.add_readonly_variable``, without the constant pool
+    pub fn readonly_variables(&self) -> &[(&'static str, Variables)] {
+        self.parent.readonly_variables()
+    }
+
     pub fn alloc_reg(&mut self, force: bool) -> Register {
         self.parent.alloc_reg(force)
     }
@@ -408,14 +713,7 @@ impl LinuxX8664 {
         self.parent.free_reg(reg)
     }
 
-    pub fn dump(
-        &self,
-    ) -> (
-        &[Instruction],
-        &[(&'static str, Variables)],
-        &[(&'static str, Variables)],
-        &[Funcs],
-    ) {
+    pub fn dump(&self) -> Dump<'_> {
         self.parent.dump()
     }
     pub fn direct_add_mut_var(&mut self, var: Variables, name: &'static str) {
@@ -431,4 +729,84 @@ impl LinuxX8664 {
     pub fn add_func(&mut self, func: Funcs) {
         self.parent.add_func(func);
     }
+    /// looks up a variable (mutable or not) by name and returns its declared value
+    pub fn get_variable(&self, name: &str) -> Option<Variables> {
+        self.parent.get_variable(name)
+    }
+    /// checks if a function with the given name was already added
+    pub fn has_func(&self, name: &str) -> bool {
+        self.parent.has_func(name)
+    }
+    /// adds a constant to ``.rodata``, writing to it faults at runtime
+    pub fn add_readonly_variable(&mut self, var: Variables, name: &'static str) {
+        self.parent.add_readonly_variable(var, name)
+    }
+    /// checks if ``name`` was added with ``add_readonly_variable``
+    pub fn is_readonly(&self, name: &str) -> bool {
+        self.parent.is_readonly(name)
+    }
+    /// moves an already added variable or function out of its default section into ``section``.
+    /// ``AsIs`` variables have no name and stay where they are
+    pub fn place_in_section(&mut self, name: &'static str, section: Section) {
+        self.parent.place_in_section(name, section)
+    }
+    /// the custom section ``name`` was placed in, if any
+    pub fn section_of(&self, name: &str) -> Option<Section> {
+        self.parent.section_of(name)
+    }
+    /// aligns an already added variable or function to ``align`` bytes, a power of two
+    pub fn set_align(&mut self, name: &'static str, align: usize) {
+        self.parent.set_align(name, align)
+    }
+    /// the alignment set with ``set_align``, if any
+    pub fn align_of(&self, name: &str) -> Option<usize> {
+        self.parent.align_of(name)
+    }
+    /// checks if ``name`` is defined anywhere in the program: a variable, a function, a ``Label``
+    /// or a label at the start of an ``AsIs`` line
+    pub fn has_symbol(&self, name: &str) -> bool {
+        self.parent.has_symbol(name)
+    }
+    /// returns a number no earlier call on this program returned (0, 1, 2, ..), for building unique labels
+    pub fn next_label_id(&mut self) -> usize {
+        self.parent.next_label_id()
+    }
+    /// returns the label of a 0 terminated read-only copy of ``text``, shared by every call with the same
+    /// text. strings ending another pooled string are emitted as a label into it
+    pub fn intern_str(&mut self, text: &str) -> &'static str {
+        self.parent.intern_str(text)
+    }
+    /// returns the label of a read-only scalar constant (or string), shared by every call with the same
+    /// bytes. arrays, structs and ``AsIs`` can not be pooled
+    pub fn intern(&mut self, value: Variables) -> &'static str {
+        self.parent.intern(value)
+    }
+    /// labels and values of the strings in the constant pool in the order they were first interned
+    pub fn pooled_strings(&self) -> &[(&'static str, Variables)] {
+        self.parent.pooled_strings()
+    }
+    /// labels and values of the scalar constants in the constant pool
+    pub fn pooled_constants(&self) -> &[(&'static str, Variables)] {
+        self.parent.pooled_constants()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LinuxX8664, Variables};
+
+    #[test]
+    fn interning_twice_returns_the_same_label() {
+        let mut x = LinuxX8664::new();
+        let hello = x.intern_str("hello");
+        let five = x.intern(Variables::I64(5));
+        assert_eq!(x.intern_str("hello"), hello);
+        assert_eq!(x.intern(Variables::Str("hello")), hello);
+        assert_eq!(x.intern(Variables::I64(5)), five);
+        assert_eq!(x.intern(Variables::U64(5)), five);
+        assert_ne!(x.intern(Variables::U32(5)), five);
+        assert_ne!(x.intern_str("hell"), hello);
+        assert_eq!(x.pooled_strings().len(), 2);
+        assert_eq!(x.pooled_constants().len(), 2);
+    }
 }
@@ -6,7 +6,9 @@ use crate::init::Register;
 /// 
 /// ### Example in Rust:
 /// ```rust
-/// linuxx86.emit(Instruction::MovImm { dst: Register::RAX, imm: 42 });
+/// use xasm_rs::{init::{LinuxX8664, Register}, instructions::Instruction};
+/// let mut linuxx86 = LinuxX8664::new();
+/// linuxx86.emit(Instruction::MovImm { dst: Register::rax, imm: 42 });
 /// ```
 #[derive(Debug)]
 pub enum Instruction {
@@ -87,7 +89,61 @@ pub enum Instruction {
     MovFromMem { addr: Register, dst: Register },
     /// Adds an immediate value directly to a register.
     AddImm { dst: Register, imm: i64 },
+    /// Atomically adds `src` to the memory at the address held in `addr`, `src` receives the old value.
+    LockXadd { addr: Register, src: Register },
+    /// Atomically compares RAX with the memory at the address held in `addr` and stores `src` there if equal.
+    /// RAX receives the old value and ZF is set on success.
+    LockCmpxchg { addr: Register, src: Register },
+    /// Atomically swaps `src` with the memory at the address held in `addr` (implicitly locked).
+    Xchg { addr: Register, src: Register },
+    /// Full memory barrier, orders all earlier loads and stores before later ones.
+    Mfence,
+    /// Jumps to `label` when `cond` holds for the current flags.
+    Jcc { cond: Condition, label: String },
+    /// Sets the low byte of `dst` to 1 when `cond` holds, otherwise to 0. The upper bytes are kept.
+    Setcc { cond: Condition, dst: Register },
+    /// Moves `src` into `dst` when `cond` holds.
+    Cmovcc { cond: Condition, dst: Register, src: Register },
     /// Inserts plain assembly code “as is” into the output.
     AsIs(&'static str),
     SYSCALL,
 }
+
+/// Condition codes for `Jcc`, `Setcc` and `Cmovcc`. The signed and unsigned variants read the flags
+/// of a `cmp a, b` as a comparison of `a` with `b`, the others test a single flag.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Condition {
+    Equal,
+    NotEqual,
+    /// Signed `a < b`.
+    Less,
+    /// Signed `a <= b`.
+    LessEqual,
+    /// Signed `a > b`.
+    Greater,
+    /// Signed `a >= b`.
+    GreaterEqual,
+    /// Unsigned `a < b`.
+    Below,
+    /// Unsigned `a <= b`.
+    BelowEqual,
+    /// Unsigned `a > b`.
+    Above,
+    /// Unsigned `a >= b`.
+    AboveEqual,
+    /// ZF set, the same test as `Equal`.
+    Zero,
+    NotZero,
+    /// SF set, the result is negative.
+    Sign,
+    NotSign,
+    /// OF set, the signed result did not fit.
+    Overflow,
+    NotOverflow,
+    /// CF set, the unsigned result did not fit (the same test as `Below`).
+    Carry,
+    NotCarry,
+    /// PF set, the low byte of the result has an even number of set bits.
+    Parity,
+    NotParity,
+}
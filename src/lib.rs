@@ -4,6 +4,8 @@ pub mod xstd;
 pub mod instructions;
 pub mod asm_makers;
 pub mod impls;
+pub mod flow;
+pub mod expr;
 
 pub fn tst(){
 }
\ No newline at end of file
@@ -1,221 +1,622 @@
-use rand::{rng, Rng};
 use crate::{
-    init::{LinuxX8664, Register, Variables},
+    impls::{xstd_mem::STRLEN, xstd_out::WRITE},
+    init::{Funcs, LinuxX8664, Register, Variables, XmmRegister},
     instructions::Instruction,
 };
 
 #[derive(Debug)]
 pub enum PrintTokens {
     TEXT(&'static str),
+    /// prints a variable based on its declared type, integers in decimal and ``Bool`` as true/false
     VAR(&'static str),
+    /// prints an integer variable in hexadecimal
+    HEX(&'static str),
+    /// prints an integer variable in binary
+    BIN(&'static str),
+    /// prints a ``F32``/``F64`` variable with the given amount of digits after the point
+    FLOAT(&'static str, u8),
+    /// prints the low double of a xmm register with the given amount of digits after the point, clobbers xmm0
+    XMM(XmmRegister, u8),
 }
 
+/// an argument of ``Xstd::xprintf``
+#[derive(Debug, Clone, Copy)]
+pub enum FmtArg {
+    /// value of a general purpose register, read before anything is printed
+    Reg(Register),
+    /// a variable, formatted by its declared type unless the spec says otherwise
+    Var(&'static str),
+    /// low double of a xmm register, clobbers xmm0
+    Xmm(XmmRegister),
+}
+
+/// a value handed to the xstd helpers
+#[derive(Debug, Clone, Copy)]
+pub enum Operand {
+    /// value of a register
+    Reg(Register),
+    /// value stored in an integer variable
+    Var(&'static str),
+    /// address of a variable
+    Addr(&'static str),
+    Imm(i64),
+}
+
+/// where an ``xswitch`` case goes
+pub enum SwitchCase<'f> {
+    /// jumps to an existing label
+    Jump(&'static str),
+    /// emits the case body in place, execution continues after the switch when it ends
+    Body(Box<dyn FnOnce(&mut Xstd<'_>) + 'f>),
+}
+
+/// digits printed after the point when a float is printed through ``PrintTokens::VAR``
+pub static DEFAULT_FLOAT_PRECISION: u8 = 6;
+
+/// a runtime routine of xstd. it is added to the program as a function the first time a call site
+/// needs it, together with everything it depends on, so programs only carry the routines they use
+#[derive(Debug)]
+pub(crate) struct Routine {
+    pub(crate) name: &'static str,
+    pub(crate) args: &'static [Register],
+    /// routines this one calls, jumps to or shares state with
+    pub(crate) deps: &'static [&'static Routine],
+    /// constants added to ``.rodata`` along with the routine
+    pub(crate) rodata: &'static [(&'static str, Variables)],
+    /// state added to ``.bss`` along with the routine
+    pub(crate) bss: &'static [(&'static str, Variables)],
+    pub(crate) body: &'static str,
+}
+
+/// prints rdi as an unsigned number in base rsi (2..=16)
+pub(crate) static PRINT_UINT: Routine = Routine {
+    name: "xstd_print_uint",
+    args: &[Register::rdi, Register::rsi],
+    deps: &[&WRITE],
+    rodata: &[],
+    bss: &[],
+    body: "
+    push rbx
+    sub rsp, 72
+    mov rax, rdi
+    mov rbx, rsi
+    lea rsi, [rsp + 72]
+    .digit:
+    xor edx, edx
+    div rbx
+    add dl, '0'
+    cmp dl, '9'
+    jbe .store
+    add dl, 39
+    .store:
+    dec rsi
+    mov [rsi], dl
+    test rax, rax
+    jnz .digit
+    lea rdx, [rsp + 72]
+    sub rdx, rsi
+    call xstd_write
+    add rsp, 72
+    pop rbx
+    ret
+",
+};
+
+/// prints rdi as a signed decimal number
+static PRINT_INT: Routine = Routine {
+    name: "xstd_print_int",
+    args: &[Register::rdi],
+    deps: &[&PRINT_UINT, &WRITE],
+    rodata: &[],
+    bss: &[],
+    body: "
+    test rdi, rdi
+    jns .positive
+    push rdi
+    push qword '-'
+    mov rsi, rsp
+    mov edx, 1
+    call xstd_write
+    add rsp, 8
+    pop rdi
+    neg rdi
+    .positive:
+    mov esi, 10
+    jmp xstd_print_uint
+",
+};
+
+/// formats the double in xmm0 with edi (at most 18) digits after the point into the buffer at rsi,
+/// rounding to nearest, and returns the length in rax. the buffer needs 330 bytes: a sign, 19 integer
+/// digits, up to 291 zeros for huge values, the point and 18 fraction digits.
+/// integer parts past 1e18 keep their leading 18 digits and are padded with zeros.
+/// xmm1-xmm3 are preserved, xmm0 is clobbered
+pub(crate) static F64_STR: Routine = Routine {
+    name: "xstd_f64_str",
+    args: &[Register::rdi, Register::rsi],
+    deps: &[],
+    rodata: &[],
+    bss: &[],
+    body: "
+    push rbx
+    push r12
+    push r13
+    push r14
+    sub rsp, 72
+    movdqu [rsp + 24], xmm1
+    movdqu [rsp + 40], xmm2
+    movdqu [rsp + 56], xmm3
+    mov r12, rsi
+    mov r14, rsi
+    mov r13d, edi
+    cmp r13, 18
+    jbe .prec_ok
+    mov r13d, 18
+    .prec_ok:
+    movq rax, xmm0
+    btr rax, 63
+    jnc .positive
+    mov byte [r12], '-'
+    inc r12
+    .positive:
+    movq xmm0, rax
+    mov rdx, 0x7ff0000000000000
+    mov rcx, rax
+    and rcx, rdx
+    cmp rcx, rdx
+    jne .finite
+    mov rdx, 0x000fffffffffffff
+    test rax, rdx
+    jnz .nan
+    mov dword [r12], 0x666e69 ; inf
+    add r12, 3
+    jmp .write
+    .nan:
+    mov r12, r14
+    mov dword [r12], 0x6e616e ; nan
+    add r12, 3
+    jmp .write
+    .finite:
+    xor ebx, ebx
+    mov rax, 1000000000000000000
+    cvtsi2sd xmm1, rax
+    mov eax, 10
+    cvtsi2sd xmm2, rax
+    .scale:
+    ucomisd xmm0, xmm1
+    jb .split
+    divsd xmm0, xmm2
+    inc rbx
+    jmp .scale
+    .split:
+    cvttsd2si rax, xmm0
+    cvtsi2sd xmm3, rax
+    subsd xmm0, xmm3
+    test rbx, rbx
+    jz .frac
+    xorpd xmm0, xmm0
+    .frac:
+    mov rcx, r13
+    mov r8d, 1
+    .pow:
+    test rcx, rcx
+    jz .pow_done
+    imul r8, r8, 10
+    dec rcx
+    jmp .pow
+    .pow_done:
+    cvtsi2sd xmm1, r8
+    mulsd xmm0, xmm1
+    cvtsd2si r9, xmm0
+    cmp r9, r8
+    jb .int_digits
+    sub r9, r8
+    inc rax
+    .int_digits:
+    mov r10d, 10
+    lea rsi, [rsp + 24]
+    .int_loop:
+    xor edx, edx
+    div r10
+    add dl, '0'
+    dec rsi
+    mov [rsi], dl
+    test rax, rax
+    jnz .int_loop
+    lea rcx, [rsp + 24]
+    sub rcx, rsi
+    mov rdi, r12
+    rep movsb
+    mov rcx, rbx
+    mov al, '0'
+    rep stosb
+    mov r12, rdi
+    test r13, r13
+    jz .write
+    mov byte [r12], '.'
+    inc r12
+    lea rsi, [r12 + r13]
+    mov rax, r9
+    mov rcx, r13
+    .frac_loop:
+    xor edx, edx
+    div r10
+    add dl, '0'
+    dec rsi
+    mov [rsi], dl
+    dec rcx
+    jnz .frac_loop
+    add r12, r13
+    .write:
+    mov rax, r12
+    sub rax, r14
+    movdqu xmm1, [rsp + 24]
+    movdqu xmm2, [rsp + 40]
+    movdqu xmm3, [rsp + 56]
+    add rsp, 72
+    pop r14
+    pop r13
+    pop r12
+    pop rbx
+    ret
+",
+};
+
+/// prints the double in xmm0 with edi (at most 18) digits after the point like ``xstd_f64_str``.
+/// xmm1-xmm3 are preserved, xmm0 is clobbered
+static PRINT_F64: Routine = Routine {
+    name: "xstd_print_f64",
+    args: &[Register::rdi],
+    deps: &[&F64_STR, &WRITE],
+    rodata: &[],
+    bss: &[],
+    body: "
+    sub rsp, 344 ; 330 bytes of buffer, keeping rsp 16 byte aligned
+    mov rsi, rsp
+    call xstd_f64_str
+    mov rsi, rsp
+    mov rdx, rax
+    call xstd_write
+    add rsp, 344
+    ret
+",
+};
+
+/// returns the ``true``/``false`` string for rdi in rax and its length in rdx
+pub(crate) static BOOL_STR: Routine = Routine {
+    name: "xstd_bool_str",
+    args: &[Register::rdi],
+    deps: &[],
+    rodata: &[("xstd_true_str", Variables::Str("true")), ("xstd_false_str", Variables::Str("false"))],
+    bss: &[],
+    body: "
+    mov rax, xstd_true_str
+    mov edx, 4
+    test rdi, rdi
+    jnz .done
+    mov rax, xstd_false_str
+    mov edx, 5
+    .done:
+    ret
+",
+};
+
+/// prints ``true`` if rdi is non zero, ``false`` otherwise
+static PRINT_BOOL: Routine = Routine {
+    name: "xstd_print_bool",
+    args: &[Register::rdi],
+    deps: &[&BOOL_STR, &WRITE],
+    rodata: &[],
+    bss: &[],
+    body: "
+    call xstd_bool_str
+    mov rsi, rax
+    jmp xstd_write
+",
+};
+
+/// prints the 0 terminated string at rdi
+pub(crate) static PRINT_STR: Routine = Routine {
+    name: "xstd_print_str",
+    args: &[Register::rdi],
+    deps: &[&STRLEN, &WRITE],
+    rodata: &[],
+    bss: &[],
+    body: "
+    push rdi
+    call xstd_strlen
+    pop rsi
+    mov rdx, rax
+    jmp xstd_write
+",
+};
+
 #[derive(Debug)]
 pub struct Xstd<'a> {
-    parent: &'a mut LinuxX8664,
+    pub(crate) parent: &'a mut LinuxX8664,
 }
 
 impl<'a> Xstd<'a> {
+    /// overwrites the mutable variable ``var_name`` with ``value`` by copying it from a new constant
     pub fn edit_mut_var(&mut self, var_name: &'static str, value: Variables) {
-        let tempname: &'static str = Box::leak(format!("temp_{}", rand::rng().random::<u32>()).into_boxed_str());
-        self.parent.add_variable(value, tempname);
-        let free_reg = self.parent.get_reg(Register::rcx, true);
-        self.parent.emit(Instruction::MovIntoVar { var_name: &tempname, reg: Register::rsi });
-        self.parent.emit(Instruction::MovIntoVar { var_name: var_name, reg: Register::rdi });
-        match value {
-            Variables::Str(ref txt) => {
-                self.parent.emit(Instruction::MovImm { dst: free_reg, imm: txt.len() as i64 });
-            }
-            Variables::I8(val) => {
-                self.parent.emit(Instruction::MovImm { dst: free_reg, imm: val as i64 });
-                self.parent.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
-            }
-            Variables::I16(val) => {
-                self.parent.emit(Instruction::MovImm { dst: free_reg, imm: val as i64 });
-                self.parent.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
-            }
-            Variables::I32(val) => {
-                self.parent.emit(Instruction::MovImm { dst: free_reg, imm: val as i64 });
-                self.parent.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
-            }
-            Variables::I64(val) => {
-                self.parent.emit(Instruction::MovImm { dst: free_reg, imm: val as i64 });
-                self.parent.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
-            }
-            Variables::U8(val) => {
-                self.parent.emit(Instruction::MovImm { dst: free_reg, imm: val as i64 });
-                self.parent.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
-            }
-            Variables::U16(val) => {
-                self.parent.emit(Instruction::MovImm { dst: free_reg, imm: val as i64 });
-                self.parent.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
-            }
-            Variables::U32(val) => {
-                self.parent.emit(Instruction::MovImm { dst: free_reg, imm: val as i64 });
-                self.parent.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
-            }
-            Variables::U64(val) => {
-                self.parent.emit(Instruction::MovImm { dst: free_reg, imm: val as i64 });
-                self.parent.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
-            }
-            Variables::Bool(val) => {
-                self.parent.emit(Instruction::MovImm { dst: free_reg, imm: val as i64 });
-                self.parent.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
-            }
-            Variables::F32(val) => {
-                self.parent.emit(Instruction::MovF { dst: free_reg, imm: val as f64 });
-                self.parent.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
-            }
-            Variables::F64(val) => {
-                self.parent.emit(Instruction::MovF { dst: free_reg, imm: val });
-                self.parent.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
-            }
-            Variables::AsIs(ref txt) => {
-                self.parent.emit(Instruction::MovIntoVar { reg: free_reg, var_name: txt });
-                self.parent.emit(Instruction::MovIntoVar { reg: free_reg, var_name });
-            }
+        if let Variables::AsIs(_) = value {
+            panic!("edit_mut_var: AsIs values can not be copied into `{}`", var_name);
         }
-        self.parent.emit(Instruction::RepRsiRdi);
-        self.parent.free_reg(free_reg);
+        let tempname: &'static str = Box::leak(format!("temp_{}", self.parent.next_label_id()).into_boxed_str());
+        self.parent.add_readonly_variable(value, tempname);
+        self.xmemcpy(Operand::Addr(var_name), Operand::Addr(tempname), Operand::Imm(value.size() as i64));
     }
 
     pub fn new(parent: &'a mut LinuxX8664) -> Self {
         Self { parent }
     }
 
+    /// adds ``routine`` and its dependencies as functions of the parent unless they are already there,
+    /// returns ``true`` if the routine was added by this call
+    pub(crate) fn use_routine(&mut self, routine: &'static Routine) -> bool {
+        if self.parent.has_func(routine.name) {
+            return false;
+        }
+        let body = routine
+            .body
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(Instruction::AsIs)
+            .collect();
+        self.parent.add_func(Funcs::new(routine.name, routine.args.to_vec(), body));
+        for (name, value) in routine.rodata {
+            self.parent.add_readonly_variable(*value, name);
+        }
+        for (name, value) in routine.bss {
+            self.parent.direct_add_mut_var(*value, name);
+        }
+        for dep in routine.deps {
+            self.use_routine(dep);
+        }
+        true
+    }
+
+    /// calls ``routine``, the caller has already loaded its arguments
+    pub(crate) fn call_routine(&mut self, routine: &'static Routine) {
+        self.use_routine(routine);
+        self.parent.emit(Instruction::Call(routine.name.to_string()));
+    }
+
+    /// loads an integer or bool variable into ``dst``, sign or zero extended to 64 bits
+    pub(crate) fn load_var(&mut self, dst: Register, var: &'static str, kind: Variables) {
+        let load = match kind {
+            Variables::I8(_) => format!("movsx {:?}, byte [{}]", dst, var),
+            Variables::I16(_) => format!("movsx {:?}, word [{}]", dst, var),
+            Variables::I32(_) => format!("movsxd {:?}, dword [{}]", dst, var),
+            Variables::U8(_) | Variables::Bool(_) => format!("movzx {}, byte [{}]", dst.dword(), var),
+            Variables::U16(_) => format!("movzx {}, word [{}]", dst.dword(), var),
+            Variables::U32(_) => format!("mov {}, dword [{}]", dst.dword(), var),
+            _ => format!("mov {:?}, qword [{}]", dst, var),
+        };
+        self.parent.emit(Instruction::AsIs(Box::leak(load.into_boxed_str())));
+    }
+
+    /// stores the low bytes of ``src`` into ``var`` according to the variable's size
+    pub(crate) fn store_var(&mut self, var: &'static str, src: Register) {
+        if self.parent.is_readonly(var) {
+            panic!("xstd: `{}` is read-only", var);
+        }
+        let size = match self.parent.get_variable(var) {
+            Some(kind) if kind.is_integer() || matches!(kind, Variables::Bool(_) | Variables::Ptr { .. }) => kind.size(),
+            _ => panic!("xstd: `{}` is not an integer or pointer variable", var),
+        };
+        let store = format!("mov [{}], {}", var, src.sized(size));
+        self.parent.emit(Instruction::AsIs(Box::leak(store.into_boxed_str())));
+    }
+
+    /// stores ``src`` into the integer variable ``var``, truncated to the variable's width
+    pub fn xstore(&mut self, var: &'static str, src: Register) {
+        self.store_var(var, src);
+    }
+
+    fn load_operand(&mut self, dst: Register, operand: Operand) {
+        match operand {
+            Operand::Reg(reg) if reg == dst => {}
+            Operand::Reg(reg) => self.parent.emit(Instruction::Mov { dst, src: reg }),
+            Operand::Var(var) => match self.parent.get_variable(var) {
+                Some(kind) if kind.is_integer() || matches!(kind, Variables::Bool(_) | Variables::Ptr { .. }) => {
+                    self.load_var(dst, var, kind)
+                }
+                _ => panic!("xstd: `{}` is not an integer or pointer variable", var),
+            },
+            Operand::Addr(var) => self.parent.emit(Instruction::MovIntoVar { reg: dst, var_name: var }),
+            Operand::Imm(imm) => self.parent.emit(Instruction::MovImm { dst, imm }),
+        }
+    }
+
+    /// loads the operands into their registers without one load clobbering a register
+    /// that a later operand still reads
+    pub(crate) fn load_args(&mut self, args: &[(Register, Operand)]) {
+        let conflict = args.iter().enumerate().any(|(i, (_, operand))| {
+            matches!(operand, Operand::Reg(reg) if args[..i].iter().any(|(dst, _)| dst == reg))
+        });
+        if !conflict {
+            for (dst, operand) in args {
+                self.load_operand(*dst, *operand);
+            }
+            return;
+        }
+        let saved: Vec<Register> = args
+            .iter()
+            .filter_map(|(dst, operand)| match operand {
+                Operand::Reg(reg) => {
+                    self.parent.emit(Instruction::Push { reg: *reg });
+                    Some(*dst)
+                }
+                _ => None,
+            })
+            .collect();
+        for (dst, operand) in args {
+            if !matches!(operand, Operand::Reg(_)) {
+                self.load_operand(*dst, *operand);
+            }
+        }
+        for dst in saved.into_iter().rev() {
+            self.parent.emit(Instruction::Pop { reg: dst });
+        }
+    }
+
+    /// prints an integer variable in the given base. signed integers are printed with a sign in base 10,
+    /// other bases print the bits of the declared width (an ``I8`` holding -1 is ``ff``)
+    fn print_int_var(&mut self, var: &'static str, kind: Variables, base: i64) {
+        let rdi_reg = self.parent.get_reg(Register::rdi, true);
+        let rsi_reg = self.parent.get_reg(Register::rsi, true);
+        let load = if base == 10 { kind } else { kind.to_unsigned() };
+        self.load_var(Register::rdi, var, load);
+        if kind.is_signed() && base == 10 {
+            self.call_routine(&PRINT_INT);
+        } else {
+            self.parent.emit(Instruction::MovImm { dst: rsi_reg, imm: base });
+            self.call_routine(&PRINT_UINT);
+        }
+        self.parent.free_reg(rdi_reg);
+        self.parent.free_reg(rsi_reg);
+    }
+
+    /// prints xmm0 as a double, the caller loads the value
+    pub(crate) fn print_xmm0(&mut self, precision: u8) {
+        let rdi_reg = self.parent.get_reg(Register::rdi, true);
+        self.parent.emit(Instruction::MovImm { dst: rdi_reg, imm: precision as i64 });
+        self.call_routine(&PRINT_F64);
+        self.parent.free_reg(rdi_reg);
+    }
+
+    /// loads a ``F32``/``F64`` variable into xmm0 as a double
+    pub(crate) fn load_float_var(&mut self, var: &'static str, kind: Variables) {
+        let load = match kind {
+            Variables::F32(_) => format!("cvtss2sd xmm0, dword [{}]", var),
+            Variables::F64(_) => format!("movsd xmm0, qword [{}]", var),
+            _ => panic!("xprint: `{}` is not a float variable", var),
+        };
+        self.parent.emit(Instruction::AsIs(Box::leak(load.into_boxed_str())));
+    }
+
+    pub(crate) fn print_float_var(&mut self, var: &'static str, kind: Variables, precision: u8) {
+        self.load_float_var(var, kind);
+        self.print_xmm0(precision);
+    }
+
+    /// adds text to the constant pool and returns its label, identical texts share one copy
+    pub(crate) fn add_text(&mut self, text: &str) -> &'static str {
+        self.parent.intern_str(text)
+    }
+
+    /// writes ``len`` bytes at ``label`` to the stdout buffer
+    pub(crate) fn write_label(&mut self, label: &'static str, len: usize) {
+        let rsi_reg = self.parent.get_reg(Register::rsi, true);
+        let rdx_reg = self.parent.get_reg(Register::rdx, true);
+        self.parent.emit(Instruction::MovIntoVar { reg: rsi_reg, var_name: label });
+        self.parent.emit(Instruction::MovImm { dst: rdx_reg, imm: len as i64 });
+        self.call_routine(&WRITE);
+        self.parent.free_reg(rsi_reg);
+        self.parent.free_reg(rdx_reg);
+    }
+
+    fn print_bool_var(&mut self, var: &'static str) {
+        let rdi_reg = self.parent.get_reg(Register::rdi, true);
+        self.load_var(Register::rdi, var, Variables::Bool(false));
+        self.call_routine(&PRINT_BOOL);
+        self.parent.free_reg(rdi_reg);
+    }
+
     pub fn setup(&mut self) {
-        self.parent.add_variable(Variables::AsIs("_newline_ : db 0x0a"), "");
-        self.parent.add_variable(Variables::AsIs("_space_ : db 0x20"), "");
+        self.parent.add_readonly_variable(Variables::AsIs("_newline_ : db 0x0a"), "");
+        self.parent.add_readonly_variable(Variables::AsIs("_space_ : db 0x20"), "");
         //self.parent.add_mutable_variable(Variables::AsIs("BUFFERADDR : resb 128"), "");
         //self.parent.emit(Instruction::AsIs("find_length:\ncmp byte [rsi + rcx], 0\nje length_found\ninc rcx\njmp find_length\nlength_found:\n"));
     }
 
-    #[allow(unused)]
+    /// prints the tokens to the stdout buffer, consecutive text is merged into a single string at
+    /// generation time. ``\\n``, ``\\t`` and ``\\\\`` are escapes in ``TEXT``, other escaped chars are dropped
     pub fn xprint(&mut self, tokens: Vec<PrintTokens>) {
-        let mut print_tok_counter = rng().random_range(0..10000);
-        for (index, token) in tokens.iter().enumerate() {
-            match token {
-                PrintTokens::TEXT(text) => {
-                    let mut word = String::new();
-                    let mut escapemode = false;
-                    for char in text.chars() {
-                        print_tok_counter = rng().random_range(0..10000);
-                        match char {
-                            '\\' if !escapemode => escapemode = !escapemode,
-                            ' ' => {
-                                if !word.is_empty() {
-                                    let rax_reg = self.parent.get_reg(Register::rax, true);
-                                    let rdi_reg = self.parent.get_reg(Register::rdi, true);
-                                    let rsi_reg = self.parent.get_reg(Register::rsi, true);
-                                    let rdx_reg = self.parent.get_reg(Register::rdx, true);
-                                    self.parent.emit(Instruction::MovImm { dst: rax_reg, imm: 1 });
-                                    self.parent.emit(Instruction::MovImm { dst: rdi_reg, imm: 1 });
-                                    let label_str = format!("print_label_{}_{}", print_tok_counter, index);
-                                    let label: &'static str = Box::leak(label_str.into_boxed_str());
-                                    let to_p_word: &'static str = Box::leak(Box::new(word.clone()));
-                                    word.clear();
-                                    self.parent.add_variable(Variables::Str(to_p_word), label);
-                                    self.parent.emit(Instruction::MovIntoVar { reg: rsi_reg, var_name: label });
-                                    self.parent.emit(Instruction::MovImm { dst: rdx_reg, imm: to_p_word.len() as i64 });
-                                    self.parent.emit(Instruction::SYSCALL);
-                                    print_tok_counter = rng().random_range(0..10000);
-                                    self.parent.free_reg(rax_reg);
-                                    self.parent.free_reg(rdi_reg);
-                                    self.parent.free_reg(rsi_reg);
-                                    self.parent.free_reg(rdx_reg);
-                                }
-                                let rax_reg = self.parent.get_reg(Register::rax, true);
-                                let rdi_reg = self.parent.get_reg(Register::rdi, true);
-                                let rsi_reg = self.parent.get_reg(Register::rsi, true);
-                                let rdx_reg = self.parent.get_reg(Register::rdx, true);
-                                self.parent.emit(Instruction::MovImm { dst: rax_reg, imm: 1 });
-                                self.parent.emit(Instruction::MovImm { dst: rdi_reg, imm: 1 });
-                                self.parent.emit(Instruction::MovIntoVar { reg: rsi_reg, var_name: "_space_" });
-                                self.parent.emit(Instruction::MovImm { dst: rdx_reg, imm: 1 });
-                                self.parent.emit(Instruction::SYSCALL);
-                                print_tok_counter = rng().random_range(0..10000);
-                                self.parent.free_reg(rax_reg);
-                                self.parent.free_reg(rdi_reg);
-                                self.parent.free_reg(rsi_reg);
-                                self.parent.free_reg(rdx_reg);
-                            }
-                            _ if escapemode => {
-                                if !word.is_empty() {
-                                    let rax_reg = self.parent.get_reg(Register::rax, true);
-                                    let rdi_reg = self.parent.get_reg(Register::rdi, true);
-                                    let rsi_reg = self.parent.get_reg(Register::rsi, true);
-                                    let rdx_reg = self.parent.get_reg(Register::rdx, true);
-                                    self.parent.emit(Instruction::MovImm { dst: rax_reg, imm: 1 });
-                                    self.parent.emit(Instruction::MovImm { dst: rdi_reg, imm: 1 });
-                                    let label_str = format!("print_label_{}_{}", print_tok_counter, index);
-                                    let label: &'static str = Box::leak(label_str.into_boxed_str());
-                                    let to_p_word: &'static str = Box::leak(Box::new(word.clone()));
-                                    word.clear();
-                                    self.parent.add_variable(Variables::Str(to_p_word), label);
-                                    self.parent.emit(Instruction::MovIntoVar { reg: rsi_reg, var_name: label });
-                                    self.parent.emit(Instruction::MovImm { dst: rdx_reg, imm: to_p_word.len() as i64 });
-                                    self.parent.emit(Instruction::SYSCALL);
-                                    print_tok_counter = rng().random_range(0..10000);
-                                    self.parent.free_reg(rax_reg);
-                                    self.parent.free_reg(rdi_reg);
-                                    self.parent.free_reg(rsi_reg);
-                                    self.parent.free_reg(rdx_reg);
-                                }
-                                if char == 'n' {
-                                    let rax_reg = self.parent.get_reg(Register::rax, true);
-                                    let rdi_reg = self.parent.get_reg(Register::rdi, true);
-                                    let rsi_reg = self.parent.get_reg(Register::rsi, true);
-                                    let rdx_reg = self.parent.get_reg(Register::rdx, true);
-                                    self.parent.emit(Instruction::MovImm { dst: rax_reg, imm: 1 });
-                                    self.parent.emit(Instruction::MovImm { dst: rdi_reg, imm: 1 });
-                                    self.parent.emit(Instruction::MovIntoVar { reg: rsi_reg, var_name: "_newline_" });
-                                    self.parent.emit(Instruction::MovImm { dst: rdx_reg, imm: 1 });
-                                    self.parent.emit(Instruction::SYSCALL);
-                                    print_tok_counter = rng().random_range(0..10000);
-                                    self.parent.free_reg(rax_reg);
-                                    self.parent.free_reg(rdi_reg);
-                                    self.parent.free_reg(rsi_reg);
-                                    self.parent.free_reg(rdx_reg);
-                                }
-                                escapemode = false;
-                            }
-                            _ => {
-                                word.push(char);
-                                print_tok_counter = rng().random_range(0..10000);
+        let mut pending = String::new();
+        for token in tokens.iter() {
+            if let PrintTokens::TEXT(text) = token {
+                let mut escapemode = false;
+                for char in text.chars() {
+                    match char {
+                        '\\' if !escapemode => escapemode = true,
+                        _ if escapemode => {
+                            match char {
+                                'n' => pending.push('\n'),
+                                't' => pending.push('\t'),
+                                '\\' => pending.push('\\'),
+                                _ => {}
                             }
+                            escapemode = false;
                         }
+                        _ => pending.push(char),
                     }
                 }
-                PrintTokens::VAR(var) => {
-                    let rax_reg = self.parent.get_reg(Register::rax, true);
-                    let rdi_reg = self.parent.get_reg(Register::rdi, true);
-                    let rsi_reg = self.parent.get_reg(Register::rsi, true);
-                    let rdx_reg = self.parent.get_reg(Register::rdx, true);
-                    let rcx_reg = self.parent.get_reg(Register::rcx, true);
-                    self.parent.emit(Instruction::MovImm { dst: rax_reg, imm: 1 });
-                    self.parent.emit(Instruction::MovImm { dst: rdi_reg, imm: 1 });
-                    self.parent.emit(Instruction::MovIntoVar { reg: rsi_reg, var_name: var });
-                    self.parent.emit(Instruction::Mov { dst: rcx_reg, src: rcx_reg });
-                    self.parent.emit(Instruction::Xor { dst: rcx_reg, src: rcx_reg });
-                    let label = format!("find_length_{}", rand::rng().random::<u32>());
-                    let asm_str = format!("{}:\n        cmp byte [rsi + rcx], 0\n        je {}_found\n        inc rcx\n        jmp {}\n        {}_found:\n", label, label, label, label);
-                    let asm_static: &'static str = Box::leak(asm_str.into_boxed_str());
-                    self.parent.emit(Instruction::AsIs(asm_static));
-                    self.parent.emit(Instruction::Mov { dst: rdx_reg, src: rcx_reg });
-                    self.parent.emit(Instruction::SYSCALL);
-                    self.parent.free_reg(rax_reg);
-                    self.parent.free_reg(rdi_reg);
-                    self.parent.free_reg(rsi_reg);
-                    self.parent.free_reg(rdx_reg);
-                    self.parent.free_reg(rcx_reg);
+                continue;
+            }
+            if !pending.is_empty() {
+                let label = self.add_text(&pending);
+                self.write_label(label, pending.len());
+                pending.clear();
+            }
+            match token {
+                PrintTokens::TEXT(_) => unreachable!(),
+                PrintTokens::HEX(var) | PrintTokens::BIN(var) => {
+                    let base = if matches!(token, PrintTokens::HEX(_)) { 16 } else { 2 };
+                    match self.parent.get_variable(var) {
+                        Some(kind) if kind.is_integer() || matches!(kind, Variables::Bool(_)) => {
+                            self.print_int_var(var, kind, base)
+                        }
+                        _ => panic!("xprint: `{}` is not an integer variable", var),
+                    }
+                }
+                PrintTokens::FLOAT(var, precision) => match self.parent.get_variable(var) {
+                    Some(kind) => self.print_float_var(var, kind, *precision),
+                    None => panic!("xprint: `{}` is not a float variable", var),
+                },
+                PrintTokens::XMM(reg, precision) => {
+                    if *reg != XmmRegister::xmm0 {
+                        let mov = format!("movsd xmm0, {:?}", reg);
+                        self.parent.emit(Instruction::AsIs(Box::leak(mov.into_boxed_str())));
+                    }
+                    self.print_xmm0(*precision);
                 }
-                
+                PrintTokens::VAR(var) => match self.parent.get_variable(var) {
+                    Some(kind) if kind.is_integer() => self.print_int_var(var, kind, 10),
+                    Some(kind @ (Variables::F32(_) | Variables::F64(_))) => {
+                        self.print_float_var(var, kind, DEFAULT_FLOAT_PRECISION)
+                    }
+                    Some(Variables::Bool(_)) => self.print_bool_var(var),
+                    Some(kind @ Variables::Array { .. }) if !kind.is_text() => {
+                        panic!("xprint: `{}` is an array, print its elements instead", var)
+                    }
+                    Some(Variables::Struct { .. }) => {
+                        panic!("xprint: `{}` is a struct, print its fields instead", var)
+                    }
+                    Some(Variables::Bytes(val)) => self.write_label(var, val.len()),
+                    Some(Variables::Ptr { .. }) => self.print_int_var(var, Variables::U64(0), 16),
+                    _ => {
+                        let rdi_reg = self.parent.get_reg(Register::rdi, true);
+                        self.parent.emit(Instruction::MovIntoVar { reg: rdi_reg, var_name: var });
+                        self.call_routine(&PRINT_STR);
+                        self.parent.free_reg(rdi_reg);
+                    }
+                },
             }
         }
+        if !pending.is_empty() {
+            let label = self.add_text(&pending);
+            self.write_label(label, pending.len());
+        }
     }
 
+    /// flushes the stdout buffer (if anything prints through it) and exits with the given code,
+    /// ending all threads of the process
     pub fn xexit(&mut self, code: i64) {
-        self.parent.emit(Instruction::MovImm { dst: Register::rax, imm: 60 });
+        self.flush_if_buffered();
+        self.parent.emit(Instruction::MovImm { dst: Register::rax, imm: 231 });
         self.parent.emit(Instruction::MovImm { dst: Register::rdi, imm: code });
         self.parent.emit(Instruction::SYSCALL);
     }